@@ -0,0 +1,187 @@
+use crate::camera::exposure::{CameraSettings, Exposure};
+use crate::camera::Camera;
+use crate::utility::vec3::*;
+
+#[derive(Debug, Copy, Clone)]
+pub struct CameraKeyframe {
+    pub time: f64, //seconds, ignored for the handles of a bezier path
+    pub lookfrom: Point3,
+    pub lookat: Point3,
+    pub vup: Vec3,
+    pub vfov: f64, //vertical field-of-view in degrees
+    pub focus_dist: f64,
+}
+
+impl CameraKeyframe {
+    pub fn new(time: f64, lookfrom: &Point3, lookat: &Point3, vfov: f64, focus_dist: f64) -> Self {
+        Self {
+            time,
+            lookfrom: *lookfrom,
+            lookat: *lookat,
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vfov,
+            focus_dist,
+        }
+    }
+
+    //weighted sum of keyframes, the weights of a spline segment sum up to 1
+    fn blend(keys: [&Self; 4], weights: [f64; 4]) -> Self {
+        let mut lookfrom = Vec3::default();
+        let mut lookat = Vec3::default();
+        let mut vup = Vec3::default();
+        let mut vfov = 0.0;
+        let mut focus_dist = 0.0;
+        for (key, weight) in keys.iter().zip(weights) {
+            lookfrom += weight * key.lookfrom;
+            lookat += weight * key.lookat;
+            vup += weight * key.vup;
+            vfov += weight * key.vfov;
+            focus_dist += weight * key.focus_dist;
+        }
+        Self {
+            time: 0.0,
+            lookfrom,
+            lookat,
+            vup: vup.unit(),
+            vfov,
+            focus_dist,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Interpolation {
+    CatmullRom, //passes through every keyframe
+    Bezier,     //key 0, 3, 6, ... are passed through, the two keys in between are handles
+}
+
+#[derive(Debug, Clone)]
+pub struct CameraPath {
+    pub keyframes: Vec<CameraKeyframe>,
+    pub interpolation: Interpolation,
+    pub aspect_ratio: f64,
+    pub aperture: f64,
+    pub fps: f64,
+    pub shutter_angle: f64, //degrees, 360 keeps the shutter open for the whole frame
+    pub exposure: Exposure, //of every frame
+}
+
+impl CameraPath {
+    pub fn new(keyframes: Vec<CameraKeyframe>, interpolation: Interpolation) -> Self {
+        match interpolation {
+            Interpolation::CatmullRom => assert!(!keyframes.is_empty(), "Empty camera path"),
+            Interpolation::Bezier => assert!(
+                keyframes.len() % 3 == 1,
+                "A bezier camera path needs 3n+1 keyframes"
+            ),
+        }
+        Self {
+            keyframes,
+            interpolation,
+            aspect_ratio: 16.0 / 9.0,
+            aperture: 0.0,
+            fps: 24.0,
+            shutter_angle: 180.0,
+            exposure: Exposure::default(),
+        }
+    }
+
+    // Frames taken with a physical camera: aspect ratio, aperture and exposure come from the
+    // settings and the shutter angle from the shutter time at the current fps, while the
+    // keyframes keep the field of view.
+    pub fn with_settings(mut self, settings: &CameraSettings) -> Self {
+        self.aspect_ratio = settings.aspect_ratio();
+        self.aperture = settings.aperture();
+        self.shutter_angle = (settings.shutter_time * self.fps * 360.0).min(360.0);
+        self.exposure = Exposure::new(settings);
+        self
+    }
+
+    //keyframes the path actually passes through
+    fn anchors(&self) -> Vec<usize> {
+        let step = match self.interpolation {
+            Interpolation::CatmullRom => 1,
+            Interpolation::Bezier => 3,
+        };
+        (0..self.keyframes.len()).step_by(step).collect()
+    }
+
+    pub fn duration(&self) -> f64 {
+        let anchors = self.anchors();
+        self.keyframes[anchors[anchors.len() - 1]].time - self.keyframes[0].time
+    }
+
+    pub fn frame_count(&self) -> usize {
+        (self.duration() * self.fps).floor() as usize + 1
+    }
+
+    pub fn frame_time(&self, frame: usize) -> f64 {
+        self.keyframes[0].time + frame as f64 / self.fps
+    }
+
+    pub fn at(&self, time: f64) -> CameraKeyframe {
+        let anchors = self.anchors();
+        let last = anchors.len() - 1;
+        if last == 0 || time <= self.keyframes[0].time {
+            return self.keyframes[0];
+        }
+        if time >= self.keyframes[anchors[last]].time {
+            return self.keyframes[anchors[last]];
+        }
+        let seg = (0..last)
+            .find(|&k| time < self.keyframes[anchors[k + 1]].time)
+            .unwrap();
+        let (start, end) = (anchors[seg], anchors[seg + 1]);
+        let t0 = self.keyframes[start].time;
+        let t1 = self.keyframes[end].time;
+        let t = (time - t0) / (t1 - t0);
+        let t2 = t * t;
+        let t3 = t2 * t;
+
+        let keys = &self.keyframes;
+        let mut key = match self.interpolation {
+            Interpolation::CatmullRom => {
+                //the end points are repeated to get a tangent there
+                let before = if start == 0 { start } else { start - 1 };
+                let after = if end == last { end } else { end + 1 };
+                CameraKeyframe::blend(
+                    [&keys[before], &keys[start], &keys[end], &keys[after]],
+                    [
+                        0.5 * (-t3 + 2.0 * t2 - t),
+                        0.5 * (3.0 * t3 - 5.0 * t2 + 2.0),
+                        0.5 * (-3.0 * t3 + 4.0 * t2 + t),
+                        0.5 * (t3 - t2),
+                    ],
+                )
+            }
+            Interpolation::Bezier => {
+                let s = 1.0 - t;
+                CameraKeyframe::blend(
+                    [&keys[start], &keys[start + 1], &keys[start + 2], &keys[end]],
+                    [s * s * s, 3.0 * s * s * t, 3.0 * s * t2, t3],
+                )
+            }
+        };
+        key.time = time;
+        key
+    }
+
+    //the shutter opens at the start of the frame, the camera is evaluated there, and the times of
+    //the path are those of the scene, in seconds
+    pub fn camera(&self, frame: usize) -> Camera {
+        let time = self.frame_time(frame);
+        let key = self.at(time);
+        let mut camera = Camera::new(
+            &key.lookfrom,
+            &key.lookat,
+            &key.vup,
+            key.vfov,
+            self.aspect_ratio,
+            self.aperture,
+            key.focus_dist,
+        );
+        camera.set_shutter(time, time + self.shutter_angle / 360.0 / self.fps);
+        camera.exposure = self.exposure;
+        camera
+    }
+}
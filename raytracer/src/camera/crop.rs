@@ -0,0 +1,78 @@
+use console::style;
+use image::RgbImage;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CropOutput {
+    Cropped, //only the region
+    Merge,   //the region replaces the same pixels of the existing output file
+}
+
+// Lossless copy of a full frame output, kept next to it on request so that merging a region
+// never decodes and encodes the rest of the frame again.
+pub fn master_path(path: &Path) -> PathBuf {
+    path.with_extension("png")
+}
+
+//normalized rectangle of the (packed) image, (0, 0) is the top left corner
+#[derive(Debug, Copy, Clone)]
+pub struct CropWindow {
+    pub x0: f64,
+    pub y0: f64,
+    pub x1: f64,
+    pub y1: f64,
+    pub output: CropOutput,
+}
+
+impl CropWindow {
+    pub fn new(x0: f64, y0: f64, x1: f64, y1: f64, output: CropOutput) -> Self {
+        Self {
+            x0: x0.min(x1).max(0.0),
+            y0: y0.min(y1).max(0.0),
+            x1: x0.max(x1).min(1.0),
+            y1: y0.max(y1).min(1.0),
+            output,
+        }
+    }
+
+    //pixel ranges covered by the window
+    pub fn pixels(&self, w: usize, h: usize) -> (Range<usize>, Range<usize>) {
+        let i0 = (self.x0 * w as f64).floor() as usize;
+        let j0 = (self.y0 * h as f64).floor() as usize;
+        let i1 = ((self.x1 * w as f64).ceil() as usize).max(i0 + 1).min(w);
+        let j1 = ((self.y1 * h as f64).ceil() as usize).max(j0 + 1).min(h);
+        (i0..i1, j0..j1)
+    }
+
+    //the cropped image, or the full frame with the region merged into its master, or into the
+    //output file itself when there is no master yet
+    pub fn apply(&self, img: RgbImage, path: &Path) -> RgbImage {
+        let (w, h) = img.dimensions();
+        let (crop_i, crop_j) = self.pixels(w as usize, h as usize);
+        let (x, y) = (crop_i.start as u32, crop_j.start as u32);
+        let (crop_w, crop_h) = (crop_i.len() as u32, crop_j.len() as u32);
+        match self.output {
+            CropOutput::Cropped => image::imageops::crop_imm(&img, x, y, crop_w, crop_h).to_image(),
+            CropOutput::Merge => {
+                let base = image::open(master_path(path)).or_else(|_| image::open(path));
+                let mut base = match base {
+                    Ok(base) if base.to_rgb8().dimensions() == (w, h) => base.to_rgb8(),
+                    _ => {
+                        println!(
+                            "{}",
+                            style("No full frame to merge into, the rest stays black.").yellow()
+                        );
+                        img.clone()
+                    }
+                };
+                for j in y..y + crop_h {
+                    for i in x..x + crop_w {
+                        base.put_pixel(i, j, *img.get_pixel(i, j));
+                    }
+                }
+                base
+            }
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use crate::utility::color::blackbody_rgb;
+use crate::utility::vec3::*;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ResponseCurve {
+    Linear,
+    Power(f64), //contrast exponent
+    Filmic,     //toe and shoulder of a film stock, normalized to white at 1.0
+}
+
+impl ResponseCurve {
+    pub fn apply(&self, x: f64) -> f64 {
+        let x = x.max(0.0);
+        match self {
+            ResponseCurve::Linear => x,
+            ResponseCurve::Power(exponent) => x.powf(*exponent),
+            ResponseCurve::Filmic => {
+                // Hable's curve, scaled so that an exposure of 1.0 maps to 1.0
+                let curve = |x: f64| {
+                    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+                    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+                };
+                let exposure_bias = 2.0;
+                curve(exposure_bias * x) / curve(exposure_bias)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct CameraSettings {
+    pub focal_length: f64,  //mm
+    pub sensor_width: f64,  //mm
+    pub sensor_height: f64, //mm
+    pub f_number: f64,
+    pub shutter_time: f64, //seconds, also the motion blur interval
+    pub iso: f64,
+    pub white_balance: f64, //kelvin, light of this temperature renders neutral
+    pub response: ResponseCurve,
+    pub units_per_meter: f64, //scene units in one metre, for the aperture size
+    pub luminance_scale: f64, //cd/m^2 represented by a radiance of 1.0
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        //full frame sensor with a normal lens, sunny day exposure
+        Self {
+            focal_length: 50.0,
+            sensor_width: 36.0,
+            sensor_height: 24.0,
+            f_number: 8.0,
+            shutter_time: 1.0 / 125.0,
+            iso: 100.0,
+            white_balance: 6500.0,
+            response: ResponseCurve::Linear,
+            units_per_meter: 1.0,
+            luminance_scale: 10000.0,
+        }
+    }
+}
+
+impl CameraSettings {
+    pub fn vfov(&self) -> f64 {
+        2.0 * (self.sensor_height / (2.0 * self.focal_length))
+            .atan()
+            .to_degrees()
+    }
+
+    pub fn aspect_ratio(&self) -> f64 {
+        self.sensor_width / self.sensor_height
+    }
+
+    //diameter of the entrance pupil in scene units
+    pub fn aperture(&self) -> f64 {
+        self.focal_length / self.f_number * 0.001 * self.units_per_meter
+    }
+
+    pub fn ev100(&self) -> f64 {
+        (self.f_number * self.f_number / self.shutter_time * 100.0 / self.iso).log2()
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Exposure {
+    pub scale: f64,
+    pub white_balance: Color, //per channel gains
+    pub response: ResponseCurve,
+}
+
+impl Default for Exposure {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            white_balance: Color::same(1.0),
+            response: ResponseCurve::Linear,
+        }
+    }
+}
+
+impl Exposure {
+    pub fn new(settings: &CameraSettings) -> Self {
+        // saturation based sensitivity: the sensor clips at 1.2 * 2^EV100 cd/m^2
+        let max_luminance = 1.2 * 2f64.powf(settings.ev100());
+        Self {
+            scale: settings.luminance_scale / max_luminance,
+            white_balance: white_balance_gains(settings.white_balance),
+            response: settings.response,
+        }
+    }
+
+    //radiance to camera response, before tone mapping
+    pub fn develop(&self, radiance: &Color) -> Color {
+        let c = self.scale * self.white_balance * *radiance;
+        Color::new(
+            self.response.apply(c.x()),
+            self.response.apply(c.y()),
+            self.response.apply(c.z()),
+        )
+    }
+}
+
+pub fn white_balance_gains(temperature: f64) -> Color {
+    let reference = blackbody_rgb(6500.0);
+    let white = blackbody_rgb(temperature);
+    //very warm black bodies fall outside of the sRGB gamut
+    let gains = Color::new(
+        reference.x() / white.x().max(1e-3),
+        reference.y() / white.y().max(1e-3),
+        reference.z() / white.z().max(1e-3),
+    );
+    gains / gains.y()
+}
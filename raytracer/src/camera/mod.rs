@@ -1,3 +1,9 @@
+pub mod animation;
+pub mod crop;
+pub mod exposure;
+pub mod stereo;
+
+use crate::camera::exposure::{CameraSettings, Exposure};
 use crate::utility::random_double_range;
 use crate::utility::ray::Ray;
 use crate::utility::vec3::*;
@@ -11,11 +17,16 @@ pub struct Camera {
     vertical: Vec3,
     u: Vec3,
     v: Vec3,
-    _w: Vec3,
+    w: Vec3,
     lens_radius: f64,
+    time0: f64, //shutter open
+    time1: f64, //shutter close
+    pub exposure: Exposure,
 }
 
 impl Camera {
+    // The shutter opens at time 0, in seconds, for the shutter time of the default settings, the
+    // exposure is left neutral so that scenes keep lighting by the brightness of their emitters.
     pub fn new(
         lookfrom: &Point3,
         lookat: &Point3,
@@ -47,17 +58,69 @@ impl Camera {
             vertical,
             u,
             v,
-            _w: w,
+            w,
             lens_radius,
+            time0: 0.0,
+            time1: CameraSettings::default().shutter_time,
+            exposure: Exposure::default(),
+        }
+    }
+
+    pub fn new_physical(
+        lookfrom: &Point3,
+        lookat: &Point3,
+        vup: &Vec3,
+        settings: &CameraSettings,
+        focus_dist: f64,
+        shutter_open: f64,
+    ) -> Self {
+        let camera = Self::new(
+            lookfrom,
+            lookat,
+            vup,
+            settings.vfov(),
+            settings.aspect_ratio(),
+            settings.aperture(),
+            focus_dist,
+        );
+        Self {
+            time0: shutter_open,
+            time1: shutter_open + settings.shutter_time,
+            exposure: Exposure::new(settings),
+            ..camera
         }
     }
 
-    pub fn get_ray(&self, s: f64, t: f64, time0: f64, time1: f64) -> Ray {
+    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
         let rd = self.lens_radius * Vec3::random_in_unit_disk();
         let offset = self.u * rd.x() + self.v * rd.y();
         let orig = self.origin + offset;
         let dir = self.lower_left_corner + s * self.horizontal + t * self.vertical - orig;
-        Ray::new(&orig, &dir, random_double_range(time0, time1))
+        Ray::new(&orig, &dir, self.shutter_time())
+    }
+
+    //angle between the rays through neighbouring pixels, for ray cones
+    pub fn pixel_spread(&self, image_height: usize) -> f64 {
+        let center = self.lower_left_corner + self.horizontal / 2.0 + self.vertical / 2.0;
+        let focus_dist = dot(&(self.origin - center), &self.w);
+        self.vertical.length() / (image_height as f64 * focus_dist)
+    }
+
+    pub fn set_shutter(&mut self, open: f64, close: f64) {
+        self.time0 = open;
+        self.time1 = close;
+    }
+
+    fn shutter_time(&self) -> f64 {
+        if self.time1 > self.time0 {
+            random_double_range(self.time0, self.time1)
+        } else {
+            self.time0
+        }
+    }
+
+    pub fn develop(&self, radiance: &Color) -> Color {
+        self.exposure.develop(radiance)
     }
 
     pub fn default_cornell_box() -> Self {
@@ -0,0 +1,191 @@
+use crate::camera::Camera;
+use crate::utility::ray::Ray;
+use std::f64::consts::PI;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Convergence {
+    Parallel, //both eyes look straight ahead
+    ToeIn,    //both eyes are rotated towards the focus point
+    OffAxis,  //parallel eyes with asymmetric frusta sharing the focus plane
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum StereoLayout {
+    SideBySide, //left eye on the left half
+    TopBottom,  //left eye on the top half
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+impl Eye {
+    fn sign(&self) -> f64 {
+        match self {
+            Eye::Left => -1.0,
+            Eye::Right => 1.0,
+        }
+    }
+}
+
+//omni-directional stereo: one equirectangular panorama per eye
+#[derive(Debug, Copy, Clone)]
+pub struct ODSCamera {
+    center: Camera,
+    eye_offset: f64, //signed half interocular distance
+}
+
+impl ODSCamera {
+    pub fn new(camera: &Camera, interocular: f64, eye: Eye) -> Self {
+        Self {
+            center: *camera,
+            eye_offset: eye.sign() * interocular / 2.0,
+        }
+    }
+
+    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
+        // s: longitude, 0.5 looks along the camera direction
+        // t: latitude, 0 is straight down and 1 straight up
+        let phi = (s - 0.5) * 2.0 * PI;
+        let theta = (t - 0.5) * PI;
+        let (u, v, w) = (self.center.u, self.center.v, self.center.w);
+        let forward = phi.sin() * u - phi.cos() * w;
+        let dir = theta.cos() * forward + theta.sin() * v;
+        // the eyes sit on a circle, always perpendicular to the horizontal view direction
+        let tangent = phi.cos() * u + phi.sin() * w;
+        let orig = self.center.origin + self.eye_offset * tangent;
+        Ray::new(&orig, &dir, self.center.shutter_time())
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum EyeCamera {
+    Perspective(Camera),
+    OmniDirectional(ODSCamera),
+}
+
+impl EyeCamera {
+    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
+        match self {
+            EyeCamera::Perspective(camera) => camera.get_ray(s, t),
+            EyeCamera::OmniDirectional(camera) => camera.get_ray(s, t),
+        }
+    }
+
+    pub fn pixel_spread(&self, image_height: usize) -> f64 {
+        match self {
+            EyeCamera::Perspective(camera) => camera.pixel_spread(image_height),
+            //the panorama spans half a turn from bottom to top
+            EyeCamera::OmniDirectional(_) => PI / image_height as f64,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct StereoCamera {
+    pub left: EyeCamera,
+    pub right: EyeCamera,
+    pub layout: StereoLayout,
+}
+
+impl StereoCamera {
+    pub fn new(
+        camera: &Camera,
+        interocular: f64,
+        convergence: Convergence,
+        layout: StereoLayout,
+    ) -> Self {
+        Self {
+            left: EyeCamera::Perspective(Self::eye_camera(
+                camera,
+                interocular,
+                convergence,
+                Eye::Left,
+            )),
+            right: EyeCamera::Perspective(Self::eye_camera(
+                camera,
+                interocular,
+                convergence,
+                Eye::Right,
+            )),
+            layout,
+        }
+    }
+
+    pub fn omni_directional(camera: &Camera, interocular: f64, layout: StereoLayout) -> Self {
+        Self {
+            left: EyeCamera::OmniDirectional(ODSCamera::new(camera, interocular, Eye::Left)),
+            right: EyeCamera::OmniDirectional(ODSCamera::new(camera, interocular, Eye::Right)),
+            layout,
+        }
+    }
+
+    fn eye_camera(camera: &Camera, interocular: f64, convergence: Convergence, eye: Eye) -> Camera {
+        let offset = eye.sign() * interocular / 2.0 * camera.u;
+        match convergence {
+            Convergence::Parallel => Camera {
+                origin: camera.origin + offset,
+                lower_left_corner: camera.lower_left_corner + offset,
+                ..*camera
+            },
+            Convergence::OffAxis => Camera {
+                origin: camera.origin + offset,
+                ..*camera
+            },
+            Convergence::ToeIn => {
+                let focus_point =
+                    camera.lower_left_corner + camera.horizontal / 2.0 + camera.vertical / 2.0;
+                let focus_dist = (focus_point - camera.origin).length();
+                let vfov = 2.0 * (camera.vertical.length() / (2.0 * focus_dist)).atan();
+                let lookfrom = camera.origin + offset;
+                let toe_in = Camera::new(
+                    &lookfrom,
+                    &focus_point,
+                    &camera.v,
+                    vfov.to_degrees(),
+                    camera.horizontal.length() / camera.vertical.length(),
+                    camera.lens_radius * 2.0,
+                    (focus_point - lookfrom).length(),
+                );
+                Camera {
+                    time0: camera.time0,
+                    time1: camera.time1,
+                    exposure: camera.exposure,
+                    ..toe_in
+                }
+            }
+        }
+    }
+
+    pub fn eye(&self, eye: Eye) -> &EyeCamera {
+        match eye {
+            Eye::Left => &self.left,
+            Eye::Right => &self.right,
+        }
+    }
+
+    //size of the packed image holding both eyes
+    pub fn image_size(&self, eye_width: usize, eye_height: usize) -> (usize, usize) {
+        match self.layout {
+            StereoLayout::SideBySide => (2 * eye_width, eye_height),
+            StereoLayout::TopBottom => (eye_width, 2 * eye_height),
+        }
+    }
+
+    //map a pixel of the packed image to its eye and the pixel inside that eye's image
+    pub fn locate(
+        &self,
+        (i, j): (usize, usize),
+        eye_width: usize,
+        eye_height: usize,
+    ) -> (EyeCamera, (usize, usize)) {
+        match self.layout {
+            StereoLayout::SideBySide if i < eye_width => (self.left, (i, j)),
+            StereoLayout::SideBySide => (self.right, (i - eye_width, j)),
+            StereoLayout::TopBottom if j < eye_height => (self.left, (i, j)),
+            StereoLayout::TopBottom => (self.right, (i, j - eye_height)),
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use crate::environment::Environment;
+use crate::hittable::sphere::get_sphere_uv;
+use crate::texture::Texture;
+use crate::utility::vec3::*;
+
+// What a ray that misses the scene finds, by its direction.
+pub trait Background: Send + Sync {
+    fn value(&self, direction: &Vec3) -> Color;
+
+    //backgrounds worth sampling along with the lights are Environments
+    fn environment(&self) -> Option<&dyn Environment> {
+        None
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SolidBackground {
+    pub color: Color,
+}
+
+impl SolidBackground {
+    pub fn new(color: &Color) -> Self {
+        Self { color: *color }
+    }
+}
+
+impl Background for SolidBackground {
+    fn value(&self, _direction: &Vec3) -> Color {
+        self.color
+    }
+}
+
+// Blend from bottom looking straight down to top looking straight up.
+#[derive(Debug, Copy, Clone)]
+pub struct GradientBackground {
+    pub bottom: Color,
+    pub top: Color,
+}
+
+impl GradientBackground {
+    pub fn new(bottom: &Color, top: &Color) -> Self {
+        Self {
+            bottom: *bottom,
+            top: *top,
+        }
+    }
+}
+
+impl Default for GradientBackground {
+    //white to sky blue, as in Ray Tracing in One Weekend
+    fn default() -> Self {
+        Self::new(&Color::new(1.0, 1.0, 1.0), &Color::new(0.5, 0.7, 1.0))
+    }
+}
+
+impl Background for GradientBackground {
+    fn value(&self, direction: &Vec3) -> Color {
+        let t = 0.5 * (direction.unit().y() + 1.0);
+        (1.0 - t) * self.bottom + t * self.top
+    }
+}
+
+// A texture on a sphere around the scene, with the uv of a Sphere and the unit direction as
+// the point, so image textures map as equirectangular images and solid ones by direction.
+#[derive(Clone, Default)]
+pub struct TextureBackground<T: Texture> {
+    pub texture: T,
+    pub intensity: f64,
+}
+
+impl<T: Texture> TextureBackground<T> {
+    pub fn new(texture: T, intensity: f64) -> Self {
+        Self { texture, intensity }
+    }
+}
+
+impl<T: Texture> Background for TextureBackground<T> {
+    fn value(&self, direction: &Vec3) -> Color {
+        let p = direction.unit();
+        let (mut u, mut v) = (0.0, 0.0);
+        get_sphere_uv(&p, &mut u, &mut v);
+        self.intensity * self.texture.value(u, v, &p)
+    }
+}
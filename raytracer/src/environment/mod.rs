@@ -0,0 +1,179 @@
+pub mod background;
+pub mod sky;
+
+use crate::environment::background::Background;
+use crate::pdf::distribution::Distribution2D;
+use crate::texture::mipmap::{MipMap, WrapMode};
+use crate::utility::color::luminance;
+use crate::utility::random_double;
+use crate::utility::vec3::*;
+use image::codecs::hdr::HdrDecoder;
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+
+// A background that lights the scene from infinitely far away, sampled along with the lights,
+// so random and pdf_value must agree with each other.
+pub trait Environment: Background {
+    //density over solid angle of the directions drawn by random
+    fn pdf_value(&self, direction: &Vec3) -> f64;
+
+    fn random(&self) -> Vec3;
+}
+
+//equirectangular coordinates of a direction, s around the Y axis as the u of a Sphere and t
+//from +Y at 0 to -Y at 1
+pub fn direction_to_st(direction: &Vec3) -> (f64, f64) {
+    let d = direction.unit();
+    let s = (f64::atan2(-d.z(), d.x()) + PI) / (2.0 * PI);
+    let t = d.y().max(-1.0).min(1.0).acos() / PI;
+    (s, t)
+}
+
+pub fn st_to_direction(s: f64, t: f64) -> Vec3 {
+    let (theta, phi) = (PI * t, 2.0 * PI * s - PI);
+    Vec3::new(
+        theta.sin() * phi.cos(),
+        theta.cos(),
+        -theta.sin() * phi.sin(),
+    )
+}
+
+//density over solid angle of a density over st
+pub fn st_pdf_to_solid_angle(pdf: f64, t: f64) -> f64 {
+    let sin_theta = (PI * t).sin();
+    if sin_theta <= 0.0 {
+        0.0
+    } else {
+        pdf / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+// An equirectangular image around the scene, in the st coordinates above. Directions are
+// drawn in proportion to the luminance of the image.
+#[derive(Clone)]
+pub struct EnvironmentMap {
+    mipmap: Arc<MipMap>,
+    distribution: Arc<Distribution2D>,
+    pub intensity: f64,
+    sin_theta: f64,
+    cos_theta: f64,
+}
+
+impl EnvironmentMap {
+    pub fn new(width: usize, height: usize, texels: Vec<[f32; 3]>) -> Self {
+        let mut func = Vec::with_capacity(width * height);
+        for (j, row) in texels.chunks(width).take(height).enumerate() {
+            //rows near the poles cover less of the sphere
+            let sin_theta = (PI * (j as f64 + 0.5) / height as f64).sin();
+            for texel in row {
+                let color = Color::new(texel[0] as f64, texel[1] as f64, texel[2] as f64);
+                func.push(luminance(&color).max(0.0) * sin_theta);
+            }
+        }
+        Self {
+            mipmap: Arc::new(MipMap::new(width, height, texels)),
+            distribution: Arc::new(Distribution2D::new(&func, width, height)),
+            intensity: 1.0,
+            sin_theta: 0.0,
+            cos_theta: 1.0,
+        }
+    }
+
+    // Radiance .hdr files keep their values, other formats are read as textures are, in [0, 1].
+    pub fn load(pathname: &str) -> Result<Self, image::ImageError> {
+        let is_hdr = Path::new(pathname)
+            .extension()
+            .map_or(false, |e| e.eq_ignore_ascii_case("hdr"));
+        if is_hdr {
+            let decoder = HdrDecoder::new(BufReader::new(File::open(pathname)?))?;
+            let meta = decoder.metadata();
+            let texels = decoder.read_image_hdr()?.into_iter().map(|p| p.0).collect();
+            return Ok(Self::new(meta.width as usize, meta.height as usize, texels));
+        }
+        let img = image::open(pathname)?.to_rgb8();
+        let (width, height) = img.dimensions();
+        let color_scale = 1.0 / 255.0;
+        let texels = img
+            .pixels()
+            .map(|p| {
+                [
+                    p[0] as f32 * color_scale,
+                    p[1] as f32 * color_scale,
+                    p[2] as f32 * color_scale,
+                ]
+            })
+            .collect();
+        Ok(Self::new(width as usize, height as usize, texels))
+    }
+
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    //turns the map around the Y axis, as RotateY does
+    pub fn with_rotation(mut self, angle: f64) -> Self {
+        let radians = angle.to_radians();
+        self.sin_theta = radians.sin();
+        self.cos_theta = radians.cos();
+        self
+    }
+
+    fn map_direction(&self, direction: &Vec3) -> Vec3 {
+        Vec3::new(
+            self.cos_theta * direction.x() - self.sin_theta * direction.z(),
+            direction.y(),
+            self.sin_theta * direction.x() + self.cos_theta * direction.z(),
+        )
+    }
+
+    fn world_direction(&self, direction: &Vec3) -> Vec3 {
+        Vec3::new(
+            self.cos_theta * direction.x() + self.sin_theta * direction.z(),
+            direction.y(),
+            -self.sin_theta * direction.x() + self.cos_theta * direction.z(),
+        )
+    }
+
+    //bilinear, wrapping around in s but not over the poles in t
+    fn lookup(&self, s: f64, t: f64) -> Color {
+        let (width, height) = (self.mipmap.width(), self.mipmap.height());
+        let x = s * width as f64 - 0.5;
+        let y = t * height as f64 - 0.5;
+        let (i, j) = (x.floor(), y.floor());
+        let (fx, fy) = (x - i, y - j);
+        let (i, j) = (i as i64, j as i64);
+        let row = |j: i64| j.max(0).min(height as i64 - 1);
+        let texel = |i: i64, j: i64| self.mipmap.texel(0, i, row(j), WrapMode::Repeat);
+        (1.0 - fx) * (1.0 - fy) * texel(i, j)
+            + fx * (1.0 - fy) * texel(i + 1, j)
+            + (1.0 - fx) * fy * texel(i, j + 1)
+            + fx * fy * texel(i + 1, j + 1)
+    }
+}
+
+impl Background for EnvironmentMap {
+    fn value(&self, direction: &Vec3) -> Color {
+        let (s, t) = direction_to_st(&self.map_direction(direction));
+        self.intensity * self.lookup(s, t)
+    }
+
+    fn environment(&self) -> Option<&dyn Environment> {
+        Some(self)
+    }
+}
+
+impl Environment for EnvironmentMap {
+    fn pdf_value(&self, direction: &Vec3) -> f64 {
+        let (s, t) = direction_to_st(&self.map_direction(direction));
+        st_pdf_to_solid_angle(self.distribution.pdf(s, t), t)
+    }
+
+    fn random(&self) -> Vec3 {
+        let ((s, t), _) = self.distribution.sample(random_double(), random_double());
+        self.world_direction(&st_to_direction(s, t))
+    }
+}
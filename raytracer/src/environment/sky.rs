@@ -0,0 +1,278 @@
+use crate::environment::background::Background;
+use crate::environment::{direction_to_st, st_pdf_to_solid_angle, st_to_direction, Environment};
+use crate::pdf::distribution::Distribution2D;
+use crate::pdf::onb::ONB;
+use crate::utility::color::{cie_xyz, luminance, xyz_to_rgb};
+use crate::utility::random_double;
+use crate::utility::vec3::*;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+const SUN_RADIUS: f64 = 0.2666; //angular radius as seen from the earth, in degrees
+const SUN_TEMPERATURE: f64 = 5778.0; //of the black body the sun shines as, in kelvin
+const SUN_LUMINANCE: f64 = 2.0e6; //before the atmosphere, in kcd/m^2 as the sky
+const SKY_RESOLUTION: (usize, usize) = (64, 32); //of the table the sky is sampled by
+
+// Clear sky of Preetham, Shirley and Smits, "A Practical Analytic Model for Daylight" (1999),
+// with the sun as a disk of the colour its light keeps through the atmosphere. Below the
+// horizon is a ground lit by both. Elevation is in degrees above the horizon and azimuth in
+// degrees around the Y axis from +X towards -Z, as the u of a Sphere. Turbidity goes from 2 for
+// a clear day to 10 for haze.
+//
+// The model gives kcd/m^2, which intensity scales into the radiance of the scene.
+#[derive(Clone)]
+pub struct PreethamSky {
+    pub sun_direction: Vec3,
+    pub turbidity: f64,
+    pub intensity: f64,
+    sun_radius: f64,          //in radians
+    sun_illuminance: Color,   //on a surface facing the sun, whatever the size of the disk
+    ground_irradiance: Color, //on the ground from the sky and the sun
+    ground_albedo: Color,
+    perez: [[f64; 5]; 3], //of Y, x and y
+    zenith: [f64; 3],     //Y, x and y looking straight up
+    distribution: Arc<Distribution2D>,
+    sun_probability: f64, //of the sun in random, the rest picks the sky
+}
+
+impl PreethamSky {
+    pub fn new(elevation: f64, azimuth: f64, turbidity: f64) -> Self {
+        //the model only holds for the sun above the horizon and a not too hazy sky
+        let elevation = elevation.max(0.0).min(90.0).to_radians();
+        let azimuth = azimuth.to_radians();
+        let t = turbidity.max(1.7).min(10.0);
+        let sun_direction = Vec3::new(
+            elevation.cos() * azimuth.cos(),
+            elevation.sin(),
+            -elevation.cos() * azimuth.sin(),
+        );
+
+        let theta_s = PI / 2.0 - elevation;
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_y = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let polynomial = |c: [[f64; 4]; 3]| {
+            let row = |r: [f64; 4]| ((r[0] * theta_s + r[1]) * theta_s + r[2]) * theta_s + r[3];
+            t * t * row(c[0]) + t * row(c[1]) + row(c[2])
+        };
+        let zenith_x = polynomial([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let zenith_chromaticity_y = polynomial([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let sun_radius = SUN_RADIUS.to_radians();
+        let sun_solid_angle = 2.0 * PI * (1.0 - sun_radius.cos());
+        let mut sky = Self {
+            sun_direction,
+            turbidity: t,
+            intensity: 0.1,
+            sun_radius,
+            sun_illuminance: sun_color(theta_s, t) * sun_solid_angle,
+            ground_irradiance: Color::default(),
+            ground_albedo: Color::same(0.3),
+            perez,
+            zenith: [zenith_y, zenith_x, zenith_chromaticity_y],
+            distribution: Arc::default(),
+            sun_probability: 0.5,
+        };
+        sky.ground_irradiance = sky.sky_irradiance() + sky.sun_illuminance * sun_direction.y();
+        sky.tabulate();
+        sky
+    }
+
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    //angular radius in degrees, a larger sun gives softer shadows but no more light
+    pub fn with_sun_size(mut self, radius: f64) -> Self {
+        self.sun_radius = radius.max(1e-3).min(90.0).to_radians();
+        self
+    }
+
+    pub fn with_ground_albedo(mut self, albedo: &Color) -> Self {
+        self.ground_albedo = *albedo;
+        self.tabulate();
+        self
+    }
+
+    //radiance of the disk, brighter as it gets smaller
+    pub fn sun_radiance(&self) -> Color {
+        self.sun_illuminance / (2.0 * PI * (1.0 - self.sun_radius.cos()))
+    }
+
+    //radiance of the sky and the ground without the sun, before intensity
+    fn sky_radiance(&self, direction: &Vec3) -> Color {
+        let d = direction.unit();
+        if d.y() < 0.0 {
+            return self.ground_albedo * self.ground_irradiance / PI;
+        }
+        let cos_theta = d.y().max(1e-3);
+        let cos_gamma = dot(&d, &self.sun_direction).max(-1.0).min(1.0);
+        let cos_theta_s = self.sun_direction.y();
+        let theta_s = cos_theta_s.max(-1.0).min(1.0).acos();
+        let perez = |c: [f64; 5], cos_theta: f64, gamma: f64, cos_gamma: f64| {
+            (1.0 + c[0] * (c[1] / cos_theta).exp())
+                * (1.0 + c[2] * (c[3] * gamma).exp() + c[4] * cos_gamma * cos_gamma)
+        };
+        let value = |i: usize| {
+            let c = self.perez[i];
+            self.zenith[i] * perez(c, cos_theta, cos_gamma.acos(), cos_gamma)
+                / perez(c, 1.0, theta_s, cos_theta_s)
+        };
+        let (big_y, x, y) = (value(0), value(1), value(2));
+        if y <= 0.0 {
+            return Color::default();
+        }
+        let xyz = Vec3::new(x / y * big_y, big_y, (1.0 - x - y) / y * big_y);
+        let rgb = xyz_to_rgb(&xyz);
+        Color::new(rgb.x().max(0.0), rgb.y().max(0.0), rgb.z().max(0.0))
+    }
+
+    //on a horizontal surface from the sky above it, by the midpoint rule
+    fn sky_irradiance(&self) -> Color {
+        let (n_phi, n_theta) = (64, 16);
+        let (d_phi, d_theta) = (2.0 * PI / n_phi as f64, PI / 2.0 / n_theta as f64);
+        let mut sum = Color::default();
+        for j in 0..n_theta {
+            let theta = (j as f64 + 0.5) * d_theta;
+            for i in 0..n_phi {
+                let phi = (i as f64 + 0.5) * d_phi;
+                let d = Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                sum += self.sky_radiance(&d) * theta.cos() * theta.sin() * d_theta * d_phi;
+            }
+        }
+        sum
+    }
+
+    //table of the sky for random, and the share of the sun in the light
+    fn tabulate(&mut self) {
+        let (width, height) = SKY_RESOLUTION;
+        let (ds, dt) = (1.0 / width as f64, 1.0 / height as f64);
+        let mut func = Vec::with_capacity(width * height);
+        let mut sky_power = 0.0;
+        for j in 0..height {
+            let t = (j as f64 + 0.5) * dt;
+            let sin_theta = (PI * t).sin();
+            for i in 0..width {
+                let s = (i as f64 + 0.5) * ds;
+                let value = luminance(&self.sky_radiance(&st_to_direction(s, t))) * sin_theta;
+                func.push(value);
+                sky_power += value * 2.0 * PI * PI * ds * dt;
+            }
+        }
+        self.distribution = Arc::new(Distribution2D::new(&func, width, height));
+        let sun_power = luminance(&self.sun_illuminance);
+        self.sun_probability = if sun_power + sky_power > 0.0 {
+            (sun_power / (sun_power + sky_power)).max(0.1).min(0.9)
+        } else {
+            0.5
+        };
+    }
+
+    fn in_sun(&self, direction: &Vec3) -> bool {
+        let d = direction.unit();
+        d.y() >= 0.0 && dot(&d, &self.sun_direction) >= self.sun_radius.cos()
+    }
+}
+
+impl Background for PreethamSky {
+    fn value(&self, direction: &Vec3) -> Color {
+        let mut radiance = self.sky_radiance(direction);
+        if self.in_sun(direction) {
+            radiance += self.sun_radiance();
+        }
+        self.intensity * radiance
+    }
+
+    fn environment(&self) -> Option<&dyn Environment> {
+        Some(self)
+    }
+}
+
+impl Environment for PreethamSky {
+    fn pdf_value(&self, direction: &Vec3) -> f64 {
+        let (s, t) = direction_to_st(direction);
+        let sky = st_pdf_to_solid_angle(self.distribution.pdf(s, t), t);
+        let sun = if self.in_sun(direction) {
+            1.0 / (2.0 * PI * (1.0 - self.sun_radius.cos()))
+        } else {
+            0.0
+        };
+        self.sun_probability * sun + (1.0 - self.sun_probability) * sky
+    }
+
+    fn random(&self) -> Vec3 {
+        if random_double() < self.sun_probability {
+            //uniform in the cone of the disk
+            let cos_theta = 1.0 - random_double() * (1.0 - self.sun_radius.cos());
+            let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+            let phi = 2.0 * PI * random_double();
+            ONB::build_from_w(&self.sun_direction).local(
+                sin_theta * phi.cos(),
+                sin_theta * phi.sin(),
+                cos_theta,
+            )
+        } else {
+            let ((s, t), _) = self.distribution.sample(random_double(), random_double());
+            st_to_direction(s, t)
+        }
+    }
+}
+
+// Radiance of the sun seen through the atmosphere at theta_s from the zenith, a black body
+// dimmed by Rayleigh scattering of the air and the Angstrom turbidity formula of the haze
+// (Preetham et al. appendix). The absorption of ozone and water vapour is left out.
+fn sun_color(theta_s: f64, turbidity: f64) -> Color {
+    //relative optical mass, of Kasten and Young
+    let m = 1.0 / (theta_s.cos() + 0.15 * (93.885 - theta_s.to_degrees()).powf(-1.253));
+    let beta = 0.04608365822050 * turbidity - 0.04586025928522;
+    let alpha = 1.3;
+    let mut xyz = Vec3::default();
+    let mut luminance_outside = 0.0;
+    for step in 0..=80 {
+        let lambda = 380.0 + 5.0 * step as f64; //in nm
+        let micrometres = lambda / 1000.0;
+        let black_body = lambda.powi(-5) / ((1.4388e7 / (lambda * SUN_TEMPERATURE)).exp() - 1.0);
+        let rayleigh = (-0.008735 * micrometres.powf(-4.08) * m).exp();
+        let aerosol = (-beta * micrometres.powf(-alpha) * m).exp();
+        let cmf = cie_xyz(lambda);
+        xyz += black_body * rayleigh * aerosol * cmf;
+        luminance_outside += black_body * cmf.y();
+    }
+    let rgb = xyz_to_rgb(&(xyz * (SUN_LUMINANCE / luminance_outside)));
+    Color::new(rgb.x().max(0.0), rgb.y().max(0.0), rgb.z().max(0.0))
+}
@@ -43,6 +43,12 @@ impl<M: Material> Hittable for XYRect<M> {
         let mut rec = HitRecord {
             p: r.at(t),
             normal: Default::default(),
+            geometric_normal: Default::default(),
+            tangent: Vec3::new(1.0, 0.0, 0.0),
+            bitangent: Vec3::new(0.0, 1.0, 0.0),
+            dpdu: Vec3::new(self.x1 - self.x0, 0.0, 0.0),
+            dpdv: Vec3::new(0.0, self.y1 - self.y0, 0.0),
+            footprint: Default::default(),
             t,
             u: (x - self.x0) / (self.x1 - self.x0),
             v: (y - self.y0) / (self.y1 - self.y0),
@@ -121,6 +127,12 @@ impl<M: Material> Hittable for XZRect<M> {
         let mut rec = HitRecord {
             p: r.at(t),
             normal: Default::default(),
+            geometric_normal: Default::default(),
+            tangent: Vec3::new(1.0, 0.0, 0.0),
+            bitangent: Vec3::new(0.0, 0.0, 1.0),
+            dpdu: Vec3::new(self.x1 - self.x0, 0.0, 0.0),
+            dpdv: Vec3::new(0.0, 0.0, self.z1 - self.z0),
+            footprint: Default::default(),
             t,
             u: (x - self.x0) / (self.x1 - self.x0),
             v: (z - self.z0) / (self.z1 - self.z0),
@@ -199,6 +211,12 @@ impl<M: Material> Hittable for YZRect<M> {
         let mut rec = HitRecord {
             p: r.at(t),
             normal: Default::default(),
+            geometric_normal: Default::default(),
+            tangent: Vec3::new(0.0, 1.0, 0.0),
+            bitangent: Vec3::new(0.0, 0.0, 1.0),
+            dpdu: Vec3::new(0.0, self.y1 - self.y0, 0.0),
+            dpdv: Vec3::new(0.0, 0.0, self.z1 - self.z0),
+            footprint: Default::default(),
             t,
             u: (y - self.y0) / (self.y1 - self.y0),
             v: (z - self.z0) / (self.z1 - self.z0),
@@ -0,0 +1,70 @@
+use crate::hittable::bvh::aabb::AABB;
+use crate::hittable::{HitRecord, Hittable};
+use crate::texture::Texture;
+use crate::utility::random_double;
+use crate::utility::ray::Ray;
+use crate::utility::vec3::*;
+
+// Leaves, fences and decals: hits where the opacity texture is too low are skipped and the
+// ray goes on to whatever lies behind. Stochastic cutouts keep a hit with the opacity as its
+// probability, which averages to soft edges over many samples.
+#[derive(Clone)]
+pub struct AlphaCutout<H: Hittable, T: Texture> {
+    pub ptr: H,
+    pub opacity: T,
+    pub threshold: f64, //hits below it are always skipped
+    pub stochastic: bool,
+}
+
+impl<H: Hittable, T: Texture> AlphaCutout<H, T> {
+    pub fn new(p: H, opacity: T, threshold: f64) -> Self {
+        Self {
+            ptr: p,
+            opacity,
+            threshold,
+            stochastic: false,
+        }
+    }
+
+    pub fn stochastic(p: H, opacity: T) -> Self {
+        Self {
+            ptr: p,
+            opacity,
+            threshold: 0.0,
+            stochastic: true,
+        }
+    }
+
+    fn opaque(&self, rec: &HitRecord) -> bool {
+        let alpha = self.opacity.scalar(rec.u, rec.v, &rec.p);
+        if alpha <= 0.0 || alpha < self.threshold {
+            return false;
+        }
+        !self.stochastic || random_double() < alpha
+    }
+}
+
+impl<H: Hittable, T: Texture> Hittable for AlphaCutout<H, T> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut t_min = t_min;
+        while let Some(rec) = self.ptr.hit(r, t_min, t_max) {
+            if self.opaque(&rec) {
+                return Some(rec);
+            }
+            t_min = rec.t + 1e-6;
+        }
+        None
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64, output_box: &mut AABB) -> bool {
+        self.ptr.bounding_box(time0, time1, output_box)
+    }
+
+    fn pdf_value(&self, o: &Point3, v: &Vec3) -> f64 {
+        self.ptr.pdf_value(o, v)
+    }
+
+    fn random(&self, o: &Vec3) -> Vec3 {
+        self.ptr.random(o)
+    }
+}
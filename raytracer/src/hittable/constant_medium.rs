@@ -1,5 +1,6 @@
 use crate::hittable::bvh::aabb::AABB;
 use crate::hittable::{HitRecord, Hittable};
+use crate::material::phase::{PhaseFunction, PhaseMaterial};
 use crate::material::{Isotropic, Material};
 use crate::texture::{SolidColor, Texture};
 use crate::utility::random_double;
@@ -34,7 +35,27 @@ impl<H: Hittable> ConstantMedium<H, Isotropic<SolidColor>> {
     }
 }
 
-impl<H: Hittable, T: Texture> Hittable for ConstantMedium<H, Isotropic<T>> {
+impl<H: Hittable, T: Texture, P: PhaseFunction> ConstantMedium<H, PhaseMaterial<T, P>> {
+    pub fn new_with_phase(b: H, d: f64, a: T, phase: P) -> Self {
+        Self {
+            boundary: b,
+            neg_inv_density: -1.0 / d,
+            phase_function: PhaseMaterial::new(a, phase),
+        }
+    }
+}
+
+impl<H: Hittable, P: PhaseFunction> ConstantMedium<H, PhaseMaterial<SolidColor, P>> {
+    pub fn new_from_color_with_phase(b: H, d: f64, c: &Color, phase: P) -> Self {
+        Self {
+            boundary: b,
+            neg_inv_density: -1.0 / d,
+            phase_function: PhaseMaterial::new_from_color(c, phase),
+        }
+    }
+}
+
+impl<H: Hittable, M: Material> Hittable for ConstantMedium<H, M> {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
         let op1 = self.boundary.hit(r, -INFINITY, INFINITY);
         op1.as_ref()?;
@@ -0,0 +1,294 @@
+use crate::hittable::bvh::aabb::AABB;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::pdf::onb::ONB;
+use crate::utility::clamp;
+use crate::utility::ray::Ray;
+use crate::utility::vec3::*;
+
+const MAX_DEPTH: i32 = 10; //limit of the subdivision when intersecting
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CurveType {
+    Flat,     //a ribbon that always faces the ray, for hair under the hair BSDF
+    Cylinder, //shaded with the normal of a round tube, for grass and thick strands
+}
+
+// Cubic Bézier segment swept with a width that varies linearly along it (Nakamaru and Ohno 2002,
+// as in pbrt). The curve is intersected as a ribbon facing the ray, by subdividing it until each
+// piece is nearly straight. u runs along the whole curve, v across it from one edge to the other.
+// The tangent of the hit record follows the curve and the bitangent points towards v.
+#[derive(Clone)]
+pub struct Curve<M: Material> {
+    pub cp: [Point3; 4], //control points of this piece
+    pub width: (f64, f64),
+    pub u_range: (f64, f64), //part of the original curve this piece covers
+    pub curve_type: CurveType,
+    pub mat: M,
+    pub bbox: AABB,
+}
+
+impl<M: Material> Curve<M> {
+    pub fn new(cp: &[Point3; 4], width: (f64, f64), curve_type: CurveType, mat: M) -> Self {
+        Self::piece(cp, width, (0.0, 1.0), curve_type, mat)
+    }
+
+    fn piece(
+        cp: &[Point3; 4],
+        width: (f64, f64),
+        u_range: (f64, f64),
+        curve_type: CurveType,
+        mat: M,
+    ) -> Self {
+        let radius = 0.5 * width.0.max(width.1);
+        let mut min = cp[0];
+        let mut max = cp[0];
+        for p in cp.iter() {
+            for i in 0..3 {
+                min[i] = min[i].min(p[i]);
+                max[i] = max[i].max(p[i]);
+            }
+        }
+        Self {
+            cp: *cp,
+            width,
+            u_range,
+            curve_type,
+            mat,
+            bbox: AABB::new(&(min - Vec3::same(radius)), &(max + Vec3::same(radius))),
+        }
+    }
+
+    //the curve cut into pieces of equal parameter length, each with a tighter bounding box
+    pub fn split(&self, pieces: usize) -> Vec<Self>
+    where
+        M: Clone,
+    {
+        let pieces = pieces.max(1);
+        (0..pieces)
+            .map(|i| {
+                let u0 = i as f64 / pieces as f64;
+                let u1 = (i + 1) as f64 / pieces as f64;
+                let cp = [
+                    blossom(&self.cp, u0, u0, u0),
+                    blossom(&self.cp, u0, u0, u1),
+                    blossom(&self.cp, u0, u1, u1),
+                    blossom(&self.cp, u1, u1, u1),
+                ];
+                let width = (self.width_at(u0), self.width_at(u1));
+                let u_range = (self.global_u(u0), self.global_u(u1));
+                Self::piece(&cp, width, u_range, self.curve_type, self.mat.clone())
+            })
+            .collect()
+    }
+
+    fn width_at(&self, u: f64) -> f64 {
+        self.width.0 + (self.width.1 - self.width.0) * u
+    }
+
+    fn global_u(&self, u: f64) -> f64 {
+        self.u_range.0 + (self.u_range.1 - self.u_range.0) * u
+    }
+
+    // Recursive test of the piece [u0, u1] given in ray space, where the ray starts at the origin
+    // and runs along z. Returns the distance along the ray and u of the closest hit.
+    fn recursive_hit(
+        &self,
+        cp: &[Vec3; 4],
+        u0: f64,
+        u1: f64,
+        z_min: f64,
+        z_max: f64,
+        depth: i32,
+    ) -> Option<(f64, f64)> {
+        //the ray must pass through the bounding box of the piece
+        let radius = 0.5 * self.width_at(u0).max(self.width_at(u1));
+        let (mut min, mut max) = (cp[0], cp[0]);
+        for p in cp.iter() {
+            for i in 0..3 {
+                min[i] = min[i].min(p[i]);
+                max[i] = max[i].max(p[i]);
+            }
+        }
+        if min.x() - radius > 0.0 || max.x() + radius < 0.0 {
+            return None;
+        }
+        if min.y() - radius > 0.0 || max.y() + radius < 0.0 {
+            return None;
+        }
+        if min.z() - radius > z_max || max.z() + radius < z_min {
+            return None;
+        }
+
+        if depth > 0 {
+            let split = subdivide(cp);
+            let um = 0.5 * (u0 + u1);
+            let first = self.recursive_hit(
+                &[split[0], split[1], split[2], split[3]],
+                u0,
+                um,
+                z_min,
+                z_max,
+                depth - 1,
+            );
+            let z_max = first.map_or(z_max, |(z, _)| z);
+            let second = self.recursive_hit(
+                &[split[3], split[4], split[5], split[6]],
+                um,
+                u1,
+                z_min,
+                z_max,
+                depth - 1,
+            );
+            return second.or(first);
+        }
+
+        //the origin must lie between the lines through the ends perpendicular to the piece
+        let edge = (cp[1].y() - cp[0].y()) * -cp[0].y() + cp[0].x() * (cp[0].x() - cp[1].x());
+        if edge < 0.0 {
+            return None;
+        }
+        let edge = (cp[2].y() - cp[3].y()) * -cp[3].y() + cp[3].x() * (cp[3].x() - cp[2].x());
+        if edge < 0.0 {
+            return None;
+        }
+
+        //closest point of the piece, taken as a straight segment
+        let (sx, sy) = (cp[3].x() - cp[0].x(), cp[3].y() - cp[0].y());
+        let denom = sx * sx + sy * sy;
+        if denom == 0.0 {
+            return None;
+        }
+        let w = clamp(-(cp[0].x() * sx + cp[0].y() * sy) / denom, 0.0, 1.0);
+        let u = u0 + (u1 - u0) * w;
+        let hit_width = self.width_at(u);
+        let (pc, _) = eval_bezier(cp, w);
+        let dist2 = pc.x() * pc.x() + pc.y() * pc.y();
+        if dist2 > hit_width * hit_width * 0.25 || pc.z() < z_min || pc.z() > z_max {
+            return None;
+        }
+
+        Some((pc.z(), u))
+    }
+}
+
+impl<M: Material> Hittable for Curve<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let length = r.direction().length();
+        let frame = ONB::build_from_w(&r.direction());
+        let cp = [
+            frame.to_local(&(self.cp[0] - r.origin())),
+            frame.to_local(&(self.cp[1] - r.origin())),
+            frame.to_local(&(self.cp[2] - r.origin())),
+            frame.to_local(&(self.cp[3] - r.origin())),
+        ];
+
+        //deep enough that the pieces are straight to within a twentieth of the width
+        let mut l0: f64 = 0.0;
+        for i in 0..2 {
+            for j in 0..3 {
+                l0 = l0.max((cp[i][j] - 2.0 * cp[i + 1][j] + cp[i + 2][j]).abs());
+            }
+        }
+        let eps = 0.05 * self.width.0.max(self.width.1);
+        let depth = if l0 > 0.0 && eps > 0.0 {
+            let r0 = (std::f64::consts::SQRT_2 * 6.0 * l0 / (8.0 * eps)).log2() / 2.0;
+            (r0.round() as i32).max(0).min(MAX_DEPTH)
+        } else {
+            0
+        };
+
+        let (z, u) = self.recursive_hit(&cp, 0.0, 1.0, t_min * length, t_max * length, depth)?;
+        let t = z / length;
+
+        let (pc, dpdu) = eval_bezier(&self.cp, u);
+        let tangent = if dpdu.length_squared() > 0.0 {
+            dpdu.unit()
+        } else {
+            (self.cp[3] - self.cp[0]).unit()
+        };
+        let d = r.direction().unit();
+        let bitangent = cross(&tangent, &d);
+        if bitangent.length_squared() < 1e-12 {
+            return None; //looking down the curve
+        }
+        let bitangent = bitangent.unit();
+        let facing = cross(&tangent, &bitangent);
+        let p = r.at(t);
+        let h = clamp(
+            dot(&(p - pc), &bitangent) / (0.5 * self.width_at(u)),
+            -1.0,
+            1.0,
+        );
+        let normal = match self.curve_type {
+            CurveType::Flat => facing,
+            CurveType::Cylinder => ((1.0 - h * h).max(0.0).sqrt() * facing + h * bitangent).unit(),
+        };
+
+        Some(HitRecord {
+            p,
+            normal,
+            geometric_normal: facing,
+            tangent,
+            bitangent,
+            dpdu: dpdu / (self.u_range.1 - self.u_range.0),
+            dpdv: self.width_at(u) * bitangent,
+            footprint: Default::default(),
+            t,
+            u: self.global_u(u),
+            v: 0.5 * (h + 1.0),
+            front_face: true,
+            mat_ptr: &self.mat,
+        })
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64, output_box: &mut AABB) -> bool {
+        *output_box = self.bbox;
+        true
+    }
+}
+
+fn lerp(t: f64, a: Vec3, b: Vec3) -> Vec3 {
+    (1.0 - t) * a + t * b
+}
+
+//point and derivative at u
+fn eval_bezier(cp: &[Vec3; 4], u: f64) -> (Vec3, Vec3) {
+    let cp1 = [
+        lerp(u, cp[0], cp[1]),
+        lerp(u, cp[1], cp[2]),
+        lerp(u, cp[2], cp[3]),
+    ];
+    let cp2 = [lerp(u, cp1[0], cp1[1]), lerp(u, cp1[1], cp1[2])];
+    let derivative = if (cp2[1] - cp2[0]).length_squared() > 0.0 {
+        3.0 * (cp2[1] - cp2[0])
+    } else {
+        //the first and last control points can coincide with their neighbours
+        cp[3] - cp[0]
+    };
+    (lerp(u, cp2[0], cp2[1]), derivative)
+}
+
+//the two halves at u = 0.5, sharing the middle point
+fn subdivide(cp: &[Vec3; 4]) -> [Vec3; 7] {
+    [
+        cp[0],
+        (cp[0] + cp[1]) / 2.0,
+        (cp[0] + 2.0 * cp[1] + cp[2]) / 4.0,
+        (cp[0] + 3.0 * cp[1] + 3.0 * cp[2] + cp[3]) / 8.0,
+        (cp[1] + 2.0 * cp[2] + cp[3]) / 4.0,
+        (cp[2] + cp[3]) / 2.0,
+        cp[3],
+    ]
+}
+
+//control point of a sub-curve by blossoming
+fn blossom(cp: &[Vec3; 4], u0: f64, u1: f64, u2: f64) -> Vec3 {
+    let a = [
+        lerp(u0, cp[0], cp[1]),
+        lerp(u0, cp[1], cp[2]),
+        lerp(u0, cp[2], cp[3]),
+    ];
+    let b = [lerp(u1, a[0], a[1]), lerp(u1, a[1], a[2])];
+    lerp(u2, b[0], b[1])
+}
@@ -1,6 +1,8 @@
 pub mod aarect;
+pub mod alpha_cutout;
 pub mod bvh;
 pub mod constant_medium;
+pub mod curve;
 pub mod mybox;
 pub mod sphere;
 pub mod triangle;
@@ -9,18 +11,25 @@ use crate::hittable::aarect::*;
 use crate::hittable::bvh::aabb::{surrounding_box, AABB};
 use crate::hittable::bvh::BVHNode;
 use crate::material::*;
+use crate::pdf::onb::ONB;
+use crate::texture::Footprint;
 use crate::utility::random_int_range;
 use crate::utility::ray::Ray;
 use crate::utility::vec3::*;
-use crate::{TIME0, TIME1};
 use std::f64::consts::PI;
 use std::f64::INFINITY;
 
 #[derive(Clone)]
 pub struct HitRecord<'a> {
-    pub p: Point3,    //hit point
-    pub normal: Vec3, //normal against ray direction
-    pub t: f64,       //optical distance
+    pub p: Point3,              //hit point
+    pub normal: Vec3,           //shading normal against ray direction
+    pub geometric_normal: Vec3, //true surface normal, on the same side as normal
+    pub tangent: Vec3,          //direction of increasing u, zero if the surface has none
+    pub bitangent: Vec3,        //direction of increasing v
+    pub dpdu: Vec3,             //change of p per unit of u, zero if unknown
+    pub dpdv: Vec3,             //change of p per unit of v
+    pub footprint: Footprint,   //of the ray in uv, set by the integrator for texture filtering
+    pub t: f64,                 //optical distance
     pub u: f64,
     pub v: f64,           //surface coordinates
     pub front_face: bool, //if ray hit to the front face
@@ -32,6 +41,12 @@ impl<'a> HitRecord<'a> {
         HitRecord {
             p: Point3::default(),
             normal: Vec3::default(),
+            geometric_normal: Vec3::default(),
+            tangent: Vec3::default(),
+            bitangent: Vec3::default(),
+            dpdu: Vec3::default(),
+            dpdv: Vec3::default(),
+            footprint: Footprint::default(),
             t: 0.0,
             u: 0.0,
             v: 0.0,
@@ -47,6 +62,25 @@ impl<'a> HitRecord<'a> {
         } else {
             -*outward_normal
         };
+        self.geometric_normal = self.normal;
+    }
+
+    //unit tangent and bitangent perpendicular to the shading normal, for tangent space maps
+    pub fn shading_tangents(&self) -> (Vec3, Vec3) {
+        let n = self.normal;
+        let t = self.tangent - dot(&self.tangent, &n) * n;
+        if t.length_squared() < 1e-12 {
+            let uvw = ONB::build_from_w(&n);
+            return (uvw.u(), uvw.v());
+        }
+        let t = t.unit();
+        let b = cross(&n, &t);
+        //keep the handedness of the uv mapping
+        if dot(&b, &self.bitangent) < 0.0 {
+            (t, -b)
+        } else {
+            (t, b)
+        }
     }
 }
 
@@ -81,9 +115,10 @@ impl HittableList {
         }
     }
 
+    //of objects that stay in place, whatever the time
     pub fn bvh(objects: HittableList) -> Self {
         let mut world = Self::new();
-        world.add(Box::new(BVHNode::new(objects, TIME0, TIME1)));
+        world.add(Box::new(BVHNode::new(objects, 0.0, 0.0)));
         world
     }
 
@@ -277,6 +312,12 @@ impl<H: Hittable> Hittable for RotateY<H> {
             rec.p = p;
             //rec.set_face_normal(&rotated_r, &normal);
             rec.normal = normal; //
+            rec.geometric_normal =
+                rotate_vec_y(&rec.geometric_normal, -self.sin_theta, self.cos_theta);
+            rec.tangent = rotate_vec_y(&rec.tangent, -self.sin_theta, self.cos_theta);
+            rec.bitangent = rotate_vec_y(&rec.bitangent, -self.sin_theta, self.cos_theta);
+            rec.dpdu = rotate_vec_y(&rec.dpdu, -self.sin_theta, self.cos_theta);
+            rec.dpdv = rotate_vec_y(&rec.dpdv, -self.sin_theta, self.cos_theta);
             return Some(rec);
         }
 
@@ -360,6 +401,12 @@ impl<H: Hittable> Hittable for RotateX<H> {
             let normal = rotate_vec_x(&rec.normal, -self.sin_theta, self.cos_theta);
             rec.p = p;
             rec.normal = normal;
+            rec.geometric_normal =
+                rotate_vec_x(&rec.geometric_normal, -self.sin_theta, self.cos_theta);
+            rec.tangent = rotate_vec_x(&rec.tangent, -self.sin_theta, self.cos_theta);
+            rec.bitangent = rotate_vec_x(&rec.bitangent, -self.sin_theta, self.cos_theta);
+            rec.dpdu = rotate_vec_x(&rec.dpdu, -self.sin_theta, self.cos_theta);
+            rec.dpdv = rotate_vec_x(&rec.dpdv, -self.sin_theta, self.cos_theta);
             return Some(rec);
         }
 
@@ -443,6 +490,12 @@ impl<H: Hittable> Hittable for RotateZ<H> {
             let normal = rotate_vec_z(&rec.normal, -self.sin_theta, self.cos_theta);
             rec.p = p;
             rec.normal = normal;
+            rec.geometric_normal =
+                rotate_vec_z(&rec.geometric_normal, -self.sin_theta, self.cos_theta);
+            rec.tangent = rotate_vec_z(&rec.tangent, -self.sin_theta, self.cos_theta);
+            rec.bitangent = rotate_vec_z(&rec.bitangent, -self.sin_theta, self.cos_theta);
+            rec.dpdu = rotate_vec_z(&rec.dpdu, -self.sin_theta, self.cos_theta);
+            rec.dpdv = rotate_vec_z(&rec.dpdv, -self.sin_theta, self.cos_theta);
             return Some(rec);
         }
 
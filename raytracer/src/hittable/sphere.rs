@@ -20,6 +20,25 @@ pub fn get_sphere_uv(p: &Point3, u: &mut f64, v: &mut f64) {
     *v = theta / PI;
 }
 
+//directions of increasing u and v at a point of the unit sphere, zero at the poles
+pub fn get_sphere_tangents(p: &Point3) -> (Vec3, Vec3) {
+    let unit_or_zero = |v: Vec3| {
+        if v.length_squared() > 1e-12 {
+            v.unit()
+        } else {
+            Vec3::default()
+        }
+    };
+    (
+        unit_or_zero(Vec3::new(p.z(), 0.0, -p.x())),
+        unit_or_zero(Vec3::new(
+            -p.x() * p.y(),
+            1.0 - p.y() * p.y(),
+            -p.y() * p.z(),
+        )),
+    )
+}
+
 #[derive(Clone)]
 pub struct Sphere<M: Material> {
     pub center: Point3,
@@ -57,6 +76,12 @@ impl<M: Material> Hittable for Sphere<M> {
         let mut rec = HitRecord {
             p: r.at(root),
             normal: Default::default(),
+            geometric_normal: Default::default(),
+            tangent: Default::default(),
+            bitangent: Default::default(),
+            dpdu: Vec3::default(),
+            dpdv: Vec3::default(),
+            footprint: Default::default(),
             t: root,
             u: 0.0,
             v: 0.0,
@@ -66,6 +91,13 @@ impl<M: Material> Hittable for Sphere<M> {
         let outward_normal = (rec.p - self.center) / self.radius;
         rec.set_face_normal(r, &outward_normal);
         get_sphere_uv(&outward_normal, &mut rec.u, &mut rec.v);
+        let (tangent, bitangent) = get_sphere_tangents(&outward_normal);
+        rec.tangent = tangent;
+        rec.bitangent = bitangent;
+        //u goes once around the equator and v from pole to pole
+        let n = outward_normal;
+        rec.dpdu = 2.0 * PI * self.radius * Vec3::new(n.z(), 0.0, -n.x());
+        rec.dpdv = PI * self.radius * bitangent;
         Some(rec)
     }
 
@@ -141,6 +173,12 @@ impl<M: Material> Hittable for MovingSphere<M> {
         let mut rec = HitRecord {
             p: r.at(root),
             normal: Default::default(),
+            geometric_normal: Default::default(),
+            tangent: Default::default(),
+            bitangent: Default::default(),
+            dpdu: Vec3::default(),
+            dpdv: Vec3::default(),
+            footprint: Default::default(),
             t: root,
             u: 0.0,
             v: 0.0,
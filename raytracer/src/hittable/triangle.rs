@@ -19,6 +19,11 @@ pub struct Triangle<M: Material> {
     pub uvab: (f64, f64),
     pub uvac: (f64, f64),
     //texture coordinate
+    pub normals: Option<[Vec3; 3]>, //vertex normals for smooth shading
+    pub tangents: [Vec3; 3],        //vertex tangents along u
+    pub bitangent_sign: f64,        //-1 if the uv mapping is mirrored
+    pub dpdu: Vec3,                 //change of the point per unit of u and v, for texture filtering
+    pub dpdv: Vec3,
 }
 
 impl<M: Material> Triangle<M> {
@@ -42,6 +47,28 @@ impl<M: Material> Triangle<M> {
             min[i] = a[i].min(b[i]).min(c[i]) - 0.000001;
             max[i] = a[i].max(b[i]).max(c[i]) + 0.000001;
         }
+
+        //solve AB = du1 T + dv1 B, AC = du2 T + dv2 B for the directions of u and v
+        let (du1, dv1, du2, dv2) = (ub - ua, vb - va, uc - ua, vc - va);
+        let det = du1 * dv2 - du2 * dv1;
+        let (dpdu, dpdv) = if det.abs() > 1e-12 {
+            ((dv2 * ab - dv1 * ac) / det, (du1 * ac - du2 * ab) / det)
+        } else {
+            (Vec3::default(), Vec3::default())
+        };
+        let (tangent, bitangent_sign) = if det.abs() > 1e-12 {
+            let (t, b) = (dpdu, dpdv);
+            let t = t - dot(&t, &n) * n;
+            let sign = if dot(&cross(&n, &t), &b) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            (t.unit(), sign)
+        } else {
+            (ab.unit(), 1.0)
+        };
+
         Self {
             a: *a,
             n,
@@ -52,9 +79,25 @@ impl<M: Material> Triangle<M> {
             uva: (ua, va),
             uvab: (ub - ua, vb - va),
             uvac: (uc - ua, vc - va),
+            normals: None,
+            tangents: [tangent; 3],
+            bitangent_sign,
+            dpdu,
+            dpdv,
         }
     }
 
+    pub fn with_normals(mut self, na: &Vec3, nb: &Vec3, nc: &Vec3) -> Self {
+        self.normals = Some([na.unit(), nb.unit(), nc.unit()]);
+        self
+    }
+
+    //tangents averaged over the triangles sharing each vertex, so shading is seamless
+    pub fn with_tangents(mut self, ta: &Vec3, tb: &Vec3, tc: &Vec3) -> Self {
+        self.tangents = [*ta, *tb, *tc];
+        self
+    }
+
     pub fn area(&self) -> f64 {
         cross(&self.pb, &self.pc).length() / 2.0
     }
@@ -94,9 +137,36 @@ impl<M: Material> Hittable for Triangle<M> {
         // AP = uAB + vAC
         if u >= 0. && v >= 0. && u + v <= 1. {
             let (x, y) = self.uv_coordinate(u, v);
+            let w = [1.0 - u - v, u, v];
+            let normal = match self.normals {
+                Some(normals) => {
+                    let ns = (w[0] * normals[0] + w[1] * normals[1] + w[2] * normals[2]).unit();
+                    //vertex normals wound against the face are flipped to its side
+                    if dot(&ns, &self.n) < 0.0 {
+                        -ns
+                    } else {
+                        ns
+                    }
+                }
+                None => self.n,
+            };
+            let tangent =
+                w[0] * self.tangents[0] + w[1] * self.tangents[1] + w[2] * self.tangents[2];
+            let tangent = tangent - dot(&tangent, &normal) * normal;
+            let tangent = if tangent.length_squared() > 1e-12 {
+                tangent.unit()
+            } else {
+                Vec3::default()
+            };
             let rec = HitRecord {
                 p,
-                normal: self.n,
+                normal,
+                geometric_normal: self.n,
+                tangent,
+                bitangent: self.bitangent_sign * cross(&normal, &tangent),
+                dpdu: self.dpdu,
+                dpdv: self.dpdv,
+                footprint: Default::default(),
                 t,
                 u: x,
                 v: y,
@@ -118,7 +188,7 @@ impl<M: Material> Hittable for Triangle<M> {
         let op = self.hit(&Ray::new(o, v, 0.0), 0.001, INFINITY);
         if let Some(rec) = op {
             let distance_squared = rec.t * rec.t * v.length_squared();
-            let cosine = (dot(v, &rec.normal) / v.length()).abs();
+            let cosine = (dot(v, &rec.geometric_normal) / v.length()).abs();
             distance_squared / (cosine * self.area())
         } else {
             0.0
@@ -1,73 +1,188 @@
 pub mod camera;
+pub mod environment;
 pub mod hittable;
 pub mod material;
+pub mod material_library;
 pub mod obj_loader;
 pub mod pdf;
 pub mod scene;
+pub mod strand_loader;
 pub mod texture;
 pub mod utility;
 
+use crate::camera::animation::CameraPath;
+use crate::camera::crop::{master_path, CropOutput, CropWindow};
+use crate::camera::stereo::{EyeCamera, StereoCamera};
+use crate::camera::Camera;
+use crate::environment::background::{Background, SolidBackground};
+use crate::environment::Environment;
 use crate::hittable::*;
-use crate::material::*;
-use crate::pdf::{HittablePDF, MixturePDF, PDF};
 use crate::scene::my_scene::*;
+use crate::texture::Footprint;
 use crate::utility::random_double;
-use crate::utility::ray::Ray;
+use crate::utility::ray::{Ray, RayCone};
 use crate::utility::vec3::*;
 use console::style;
 use image::{ImageBuffer, RgbImage};
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use rand::seq::SliceRandom;
 use std::f64::INFINITY;
+use std::ops::Range;
 use std::sync::{mpsc, Arc};
 use std::{fs::File, process::exit, thread};
 
-const MAX_LEN: usize = 4000;
-const TIME0: f64 = 0.0;
-const TIME1: f64 = 1.0;
-
 fn main() {
     let path = std::path::Path::new("output/works/final-work-edge-detect.jpg");
     let prefix = path.parent().unwrap();
     std::fs::create_dir_all(prefix).expect("Cannot create all the parents");
 
-    let threads_number: usize = 10;
-    let shuffle: bool = true;
-
     //Image
     let aspect_ratio = 16.0 / 9.0;
     let width: usize = 3840;
-    let samples_per_pixel: u32 = 100;
-    let max_bounce_depth: i32 = 50;
-    let height = (width as f64 / aspect_ratio) as usize;
-
-    let edge_detect: bool = true;
-    let edge_detect_level = 72.0; //high: 64, low: 128
+    let settings = RenderSettings {
+        width,
+        height: (width as f64 / aspect_ratio) as usize,
+        samples_per_pixel: 100,
+        max_bounce_depth: 50,
+        threads_number: 10,
+        shuffle: true,
+        edge_detect: true,
+        edge_detect_level: 72.0, //high: 64, low: 128
+        crop: None,
+        // crop: Some(CropWindow::new(0.55, 0.2, 0.75, 0.4, CropOutput::Merge)),
+        lossless_master: false,
+    };
+    let quality = 100;
 
     //World
     let (world, camera) = final_work();
-    let background = Color::new(0.5, 0.7, 1.0) * 0.8;
+
+    //Background: what camera rays that miss the scene see, and the light the other rays find
+    //there. Environments among them are sampled along with the lights.
+    let background: Arc<dyn Background> =
+        Arc::new(SolidBackground::new(&(Color::new(0.5, 0.7, 1.0) * 0.8)));
+    // let background: Arc<dyn Background> = Arc::new(GradientBackground::default());
+    let lighting = background.clone();
+    // let lighting: Arc<dyn Background> = Arc::new(
+    //     EnvironmentMap::load("raytracer/sources/sky.hdr")
+    //         .expect("Cannot load the environment map")
+    //         .with_rotation(90.0)
+    //         .with_intensity(1.0),
+    // );
+    // let lighting: Arc<dyn Background> =
+    //     Arc::new(PreethamSky::new(35.0, 120.0, 3.0).with_intensity(0.1));
 
     //Lights
     let lights = HittableList::default();
 
-    //Render
-    let quality = 100;
-    let mut img: RgbImage = ImageBuffer::new(width as u32, height as u32);
+    //Stereo: both eyes are packed into one image, width and height are per eye
+    let stereo: fn(&Camera) -> Option<StereoCamera> = |_| None;
+    // let stereo: fn(&Camera) -> Option<StereoCamera> = |camera| {
+    //     Some(StereoCamera::new(
+    //         camera,
+    //         2.0,
+    //         Convergence::OffAxis,
+    //         StereoLayout::SideBySide,
+    //     ))
+    // };
+
+    //Animation: render a range of frames as a numbered image sequence
+    let animation: Option<(CameraPath, Range<usize>)> = None;
+    // let animation = Some((final_work_flight(), 0..final_work_flight().frame_count()));
+
+    let world = Arc::new(world);
+    let lights = Arc::new(lights);
+
+    match animation {
+        Some((camera_path, frames)) => {
+            for frame in frames {
+                let camera = camera_path.camera(frame);
+                let img = render(
+                    &world,
+                    &lights,
+                    &camera,
+                    stereo(&camera),
+                    &background,
+                    &lighting,
+                    &settings,
+                );
+                let stem = path.file_stem().unwrap().to_str().unwrap();
+                let frame_path = path.with_file_name(format!("{}-{:04}.jpg", stem, frame));
+                output(img, &frame_path, quality, &settings);
+            }
+        }
+        None => {
+            let img = render(
+                &world,
+                &lights,
+                &camera,
+                stereo(&camera),
+                &background,
+                &lighting,
+                &settings,
+            );
+            output(img, path, quality, &settings);
+        }
+    }
+
+    exit(0);
+}
+
+struct RenderSettings {
+    width: usize,
+    height: usize,
+    samples_per_pixel: u32,
+    max_bounce_depth: i32,
+    threads_number: usize,
+    shuffle: bool,
+    edge_detect: bool,
+    edge_detect_level: f64,
+    crop: Option<CropWindow>,
+    lossless_master: bool, //also keep full frames as PNG, for crop windows to merge into
+}
+
+fn render(
+    world: &Arc<HittableList>,
+    lights: &Arc<HittableList>,
+    camera: &Camera,
+    stereo: Option<StereoCamera>,
+    background: &Arc<dyn Background>,
+    lighting: &Arc<dyn Background>,
+    settings: &RenderSettings,
+) -> RgbImage {
+    let (width, height) = (settings.width, settings.height);
+    let samples_per_pixel = settings.samples_per_pixel;
+    let max_bounce_depth = settings.max_bounce_depth;
+    let camera = *camera;
+
+    let (image_width, image_height) = match stereo {
+        Some(stereo) => stereo.image_size(width, height),
+        None => (width, height),
+    };
+
+    let mut img: RgbImage = ImageBuffer::new(image_width as u32, image_height as u32);
+
+    //Crop Window: pixels are still addressed in the full frame, so the samples do not change
+    let (crop_i, crop_j) = match settings.crop {
+        Some(crop) => crop.pixels(image_width, image_height),
+        None => (0..image_width, 0..image_height),
+    };
+    //edge detection needs the neighbours of the border pixels
+    let margin = if settings.edge_detect { 1 } else { 0 };
+    let trace_i = crop_i.start.saturating_sub(margin)..(crop_i.end + margin).min(image_width);
+    let trace_j = crop_j.start.saturating_sub(margin)..(crop_j.end + margin).min(image_height);
 
     //for edge detection
-    let mut rgb_table = vec![[[0u8; 3]; MAX_LEN]; MAX_LEN];
-    let mut gray_table = vec![[0u8; MAX_LEN]; MAX_LEN];
+    let mut rgb_table = vec![vec![[0u8; 3]; image_height]; image_width];
+    let mut gray_table = vec![vec![0u8; image_height]; image_width];
 
     //Multi Threads
     let multi_progress_bar = MultiProgress::new();
-    let (pixel_list, pixels_per_thread) = pixel_allocate(width, height, threads_number, shuffle);
+    let (pixel_list, pixels_per_thread) =
+        pixel_allocate(trace_i, trace_j, settings.threads_number, settings.shuffle);
     let mut threads = Vec::new();
     let mut recv = Vec::new();
 
-    let world = Arc::new(world);
-    let lights = Arc::new(lights);
-
     for (_k, pixels) in pixel_list.iter().enumerate() {
         let (tx, rx) = mpsc::channel();
         recv.push(rx);
@@ -77,6 +192,8 @@ fn main() {
         let pixels = pixels.clone();
         //let lights = Arc::new(lights.clone()) as Arc<dyn Hittable>;
         let lights = lights.clone();
+        let background = background.clone();
+        let lighting = lighting.clone();
         let pb = multi_progress_bar.add(ProgressBar::new(pixels_per_thread));
         pb.set_style(ProgressStyle::default_bar()
             .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] [{pos}/{len}] ({eta})")
@@ -84,17 +201,23 @@ fn main() {
         let handle = thread::spawn(move || {
             for pixel in pixels {
                 let mut pixel_color = Color::default();
+                let (view, (i, j)) = match stereo {
+                    Some(stereo) => stereo.locate(pixel, width, height),
+                    None => (EyeCamera::Perspective(camera), pixel),
+                };
+                let spread = view.pixel_spread(height);
                 for _s in 0..samples_per_pixel {
-                    let u = ((pixel.0 as f64) + random_double()) / ((width - 1) as f64);
-                    let v =
-                        (((height - pixel.1 - 1) as f64) + random_double()) / ((height - 1) as f64);
-                    let r = camera.get_ray(u, v, TIME0, TIME1);
+                    let u = ((i as f64) + random_double()) / ((width - 1) as f64);
+                    let v = (((height - j - 1) as f64) + random_double()) / ((height - 1) as f64);
+                    let r = view.get_ray(u, v).with_cone(RayCone::new(0.0, spread));
                     pixel_color += ray_color(
                         &r,
-                        &background,
+                        background.as_ref(),
+                        lighting.as_ref(),
                         world.as_ref(),
                         lights.as_ref(),
                         max_bounce_depth,
+                        Arrival::Camera,
                     );
                 }
                 pixel_color_list.push((pixel, pixel_color));
@@ -117,22 +240,23 @@ fn main() {
     for receiver in &recv {
         let pixel_color_list = receiver.recv().unwrap();
         for ((i, j), pixel_color) in pixel_color_list {
-            if edge_detect {
-                rgb_table[i][j] = pixel_color.multi_samples_rgb(samples_per_pixel);
+            let pixel_color = camera.develop(&(pixel_color / samples_per_pixel as f64));
+            if settings.edge_detect {
+                rgb_table[i][j] = pixel_color.multi_samples_rgb(1);
                 gray_table[i][j] = gray_color(&rgb_table[i][j]);
             } else {
                 let pixel = img.get_pixel_mut(i as u32, j as u32);
-                *pixel = image::Rgb(pixel_color.multi_samples_rgb(samples_per_pixel));
+                *pixel = image::Rgb(pixel_color.multi_samples_rgb(1));
             }
         }
     }
 
-    if edge_detect {
+    if settings.edge_detect {
         let wx: [i32; 9] = [-1, 0, 1, -2, -0, 2, -1, 0, 1];
         let wy: [i32; 9] = [-1, -2, -1, 0, 0, 0, 1, 2, 1];
 
-        for j in 1..height - 1 {
-            for i in 1..width - 1 {
+        for j in crop_j.start.max(1)..crop_j.end.min(image_height - 1) {
+            for i in crop_i.start.max(1)..crop_i.end.min(image_width - 1) {
                 let rgb = vec![
                     gray_table[i - 1][j - 1],
                     gray_table[i - 1][j],
@@ -152,7 +276,7 @@ fn main() {
                 }
                 let g = ((gx * gx + gy * gy) as f64).sqrt();
                 let pixel = img.get_pixel_mut(i as u32, j as u32);
-                *pixel = image::Rgb(if g > edge_detect_level {
+                *pixel = image::Rgb(if g > settings.edge_detect_level {
                     [0, 0, 0]
                 } else {
                     rgb_table[i][j]
@@ -161,6 +285,20 @@ fn main() {
         }
     }
 
+    img
+}
+
+fn output(img: RgbImage, path: &std::path::Path, quality: u8, settings: &RenderSettings) {
+    let crop = settings.crop;
+    let img = match crop {
+        Some(crop) => crop.apply(img, path),
+        None => img,
+    };
+    if settings.lossless_master && crop.map_or(true, |crop| crop.output == CropOutput::Merge) {
+        if let Err(e) = img.save(master_path(path)) {
+            println!("{} {}", style("Saving the lossless master fails:").red(), e);
+        }
+    }
     println!(
         "Output image as \"{}\"",
         style(path.to_str().unwrap()).yellow()
@@ -171,78 +309,161 @@ fn main() {
         Ok(_) => {}
         Err(_) => println!("{}", style("Outputting image fails.").red()),
     }
-
-    exit(0);
 }
 
 //----------------------------------------------------------------------------------------------
 
+//how the ray traced by ray_color was made
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Arrival {
+    Camera,
+    Unweighted, //by a specular bounce or without a light sample, the light it finds counts fully
+    Sampled(f64), //by a material sample of this density, competing with the light sample
+}
+
+// Each hit adds its emission, a light sample towards the lights and the environment of the
+// lighting background, and the light along a sample of the material. Both samples can find the
+// lights, so they are weighted by multiple importance sampling with the power heuristic.
 fn ray_color(
     r: &Ray,
-    background: &Color,
+    background: &dyn Background,
+    lighting: &dyn Background,
     world: &impl Hittable,
     lights: &impl Hittable,
     depth: i32,
+    arrival: Arrival,
 ) -> Color {
     if depth <= 0 {
         // If we've exceeded the ray bounce limit, no more light is gathered.
         return Color::default();
     }
-    let rec_op = world.hit(r, 0.001, INFINITY);
-    if rec_op.is_none() {
-        // If the ray hits nothing, return the background color.
-        return *background;
-    }
-    let rec = rec_op.unwrap();
+    let environment = lighting.environment();
+    let mut rec = match world.hit(r, 0.001, INFINITY) {
+        Some(rec) => rec,
+        None => {
+            // If the ray hits nothing, camera rays see the background and the others its light.
+            if arrival == Arrival::Camera {
+                return background.value(&r.direction());
+            }
+            let mut light = lighting.value(&r.direction());
+            if let Arrival::Sampled(pdf) = arrival {
+                let light_pdf = light_pdf_value(lights, environment, &r.origin(), &r.direction());
+                light *= power_heuristic(pdf, light_pdf);
+            }
+            return light;
+        }
+    };
+    rec.footprint = Footprint::new(r, &rec);
 
-    let mut srec = ScatterRecord::default();
-    let emitted = rec.mat_ptr.emitted(r, &rec, rec.u, rec.v, &rec.p);
-    if !rec.mat_ptr.scatter(r, &rec, &mut srec) {
-        return emitted;
+    let mut emitted = rec.mat_ptr.emitted(r, &rec, rec.u, rec.v, &rec.p);
+    if let Arrival::Sampled(pdf) = arrival {
+        let light_pdf = light_pdf_value(lights, environment, &r.origin(), &r.direction());
+        emitted *= power_heuristic(pdf, light_pdf);
     }
 
-    if srec.is_specular {
-        return srec.attenuation
-            * ray_color(&srec.specular_ray, background, world, lights, depth - 1);
+    let light_sampled =
+        (!lights.empty() || environment.is_some()) && rec.mat_ptr.lobes().non_specular();
+    let mut color = emitted;
+    if light_sampled {
+        let direction = light_random(lights, environment, &rec.p);
+        let to_light = Ray::new(&rec.p, &direction, r.time());
+        let light_pdf = light_pdf_value(lights, environment, &rec.p, &direction);
+        let f = rec.mat_ptr.eval(r, &rec, &to_light);
+        if light_pdf > 0.0 && f.length_squared() > 0.0 {
+            let light = match world.hit(&to_light, 0.001, INFINITY) {
+                Some(light_rec) => light_rec.mat_ptr.emitted(
+                    &to_light,
+                    &light_rec,
+                    light_rec.u,
+                    light_rec.v,
+                    &light_rec.p,
+                ),
+                None => lighting.value(&direction),
+            };
+            let weight = power_heuristic(light_pdf, rec.mat_ptr.pdf(r, &rec, &to_light));
+            color += f * light * weight / light_pdf;
+        }
     }
 
-    let light_pdf = HittablePDF::new(lights, &rec.p);
-    let cos_pdf_box = srec.pdf_ptr.unwrap();
-    let cos_pdf_ptr = cos_pdf_box.as_ref();
-    let mixed_pdf = MixturePDF::new(&light_pdf, cos_pdf_ptr);
+    match rec.mat_ptr.sample(r, &rec) {
+        Some(sample) => {
+            let distance = rec.t * r.direction().length();
+            let cone = r.cone.bounce(distance, sample.lobe.is_specular());
+            let scattered = sample.scattered.with_cone(cone);
+            let arrival = if light_sampled && !sample.lobe.is_specular() {
+                Arrival::Sampled(sample.pdf)
+            } else {
+                Arrival::Unweighted
+            };
+            let light = ray_color(
+                &scattered,
+                background,
+                lighting,
+                world,
+                lights,
+                depth - 1,
+                arrival,
+            );
+            color + sample.weight * light
+        }
+        None => color,
+    }
+}
 
-    let pdf_ptr = if lights.empty() {
-        cos_pdf_ptr as &dyn PDF
-    } else {
-        &mixed_pdf as &dyn PDF
-    };
+//the light sample picks the lights or the environment evenly when there are both
+fn light_random(
+    lights: &impl Hittable,
+    environment: Option<&dyn Environment>,
+    origin: &Point3,
+) -> Vec3 {
+    match environment {
+        Some(env) if lights.empty() || random_double() < 0.5 => env.random(),
+        _ => lights.random(origin),
+    }
+}
 
-    let scattered = Ray::new(&rec.p, &pdf_ptr.generate(), r.time());
-    let pdf_val = pdf_ptr.value(&scattered.direction());
+fn light_pdf_value(
+    lights: &impl Hittable,
+    environment: Option<&dyn Environment>,
+    origin: &Point3,
+    direction: &Vec3,
+) -> f64 {
+    match (lights.empty(), environment) {
+        (true, None) => 0.0,
+        (true, Some(env)) => env.pdf_value(direction),
+        (false, None) => lights.pdf_value(origin, direction),
+        (false, Some(env)) => {
+            0.5 * (lights.pdf_value(origin, direction) + env.pdf_value(direction))
+        }
+    }
+}
 
-    emitted
-        + srec.attenuation
-            * rec.mat_ptr.scattering_pdf(r, &rec, &scattered)
-            * ray_color(&scattered, background, world, lights, depth - 1)
-            / pdf_val
+//weight of a sample drawn with density pdf against another strategy with density other
+fn power_heuristic(pdf: f64, other: f64) -> f64 {
+    if other <= 0.0 {
+        return 1.0;
+    }
+    let (a, b) = (pdf * pdf, other * other);
+    a / (a + b)
 }
 
 fn pixel_allocate(
-    w: usize,
-    h: usize,
+    w: Range<usize>,
+    h: Range<usize>,
     threads_num: usize,
     shuffle: bool,
 ) -> (Vec<Vec<(usize, usize)>>, u64) {
-    let mut pixels_per_thread = (w * h) / threads_num;
-    if (w * h) % threads_num > 0 {
+    let total = w.len() * h.len();
+    let mut pixels_per_thread = total / threads_num;
+    if total % threads_num > 0 {
         pixels_per_thread += 1;
     }
 
     let mut pixel_set = vec![Vec::new(); threads_num];
 
     let mut all_pixels = Vec::new();
-    for j in 0..h {
-        for i in 0..w {
+    for j in h {
+        for i in w.clone() {
             all_pixels.push((i, j));
         }
     }
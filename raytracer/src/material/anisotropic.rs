@@ -0,0 +1,122 @@
+use crate::hittable::HitRecord;
+use crate::material::conductor::{Conductor, ConductorBSDF};
+use crate::material::microfacet::*;
+use crate::material::{shading_frame, BSDFSample, Lobe, Material, BSDF};
+use crate::pdf::onb::ONB;
+use crate::pdf::PDF;
+use crate::texture::{SolidColor, Texture};
+use crate::utility::ray::Ray;
+use crate::utility::vec3::*;
+
+// Brushed metal: a conductor whose roughness differs along the tangent and the bitangent.
+// The grooves of the brushing run along the smoother direction and stretch the highlights
+// across it. The tangent comes from the uv mapping, or from a tangent map that holds the
+// brushing direction in tangent space encoded like a normal map.
+#[derive(Clone)]
+pub struct Anisotropic<T: Texture> {
+    pub eta: Color,
+    pub k: Color,
+    pub roughness_u: f64, //along the tangent
+    pub roughness_v: f64, //along the bitangent
+    pub distribution: Distribution,
+    pub tangent_map: Option<T>,
+}
+
+impl Anisotropic<SolidColor> {
+    pub fn new(eta: &Color, k: &Color, roughness_u: f64, roughness_v: f64) -> Self {
+        Self {
+            eta: *eta,
+            k: *k,
+            roughness_u,
+            roughness_v,
+            distribution: Distribution::GGX,
+            tangent_map: None,
+        }
+    }
+
+    //brushed along the tangent
+    pub fn brushed_aluminium() -> Self {
+        let aluminium = Conductor::aluminium();
+        Self::new(&aluminium.eta, &aluminium.k, 0.1, 0.45)
+    }
+}
+
+impl<T: Texture> Anisotropic<T> {
+    pub fn with_tangent_map<U: Texture>(self, map: U) -> Anisotropic<U> {
+        Anisotropic {
+            eta: self.eta,
+            k: self.k,
+            roughness_u: self.roughness_u,
+            roughness_v: self.roughness_v,
+            distribution: self.distribution,
+            tangent_map: Some(map),
+        }
+    }
+
+    fn smooth(&self) -> bool {
+        let (ru, rv) = (self.roughness_u, self.roughness_v);
+        ru * ru < 1e-3 && rv * rv < 1e-3
+    }
+
+    //shading frame with u along the brushing direction
+    fn frame(&self, r_in: &Ray, rec: &HitRecord) -> ONB {
+        let (uvw, _) = shading_frame(r_in, rec);
+        let (t, b) = rec.shading_tangents();
+        let tangent = match &self.tangent_map {
+            Some(map) => {
+                let c = 2.0 * map.value_at(rec) - Color::same(1.0);
+                c.x() * t + c.y() * b
+            }
+            None => t,
+        };
+        ONB::build_from_wu(&uvw.w(), &tangent)
+    }
+
+    fn bsdf(&self, r_in: &Ray, rec: &HitRecord) -> ConductorBSDF {
+        let uvw = self.frame(r_in, rec);
+        let microfacet = Microfacet::anisotropic(
+            self.distribution,
+            self.roughness_u * self.roughness_u,
+            self.roughness_v * self.roughness_v,
+        );
+        let wo = uvw.to_local(&(-r_in.direction().unit()));
+        ConductorBSDF::new(uvw, wo, microfacet, &self.eta, &self.k, None)
+    }
+}
+
+impl<T: Texture> Material for Anisotropic<T> {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BSDFSample> {
+        if self.smooth() {
+            //too sharp for light sampling to ever hit the lobe
+            let uvw = self.frame(r_in, rec);
+            let unit_dir = r_in.direction().unit();
+            let cos_theta = dot(&(-unit_dir), &uvw.w());
+            let scattered = Ray::new(&rec.p, &reflect(&unit_dir, &uvw.w()), r_in.time());
+            let weight = fresnel_conductor(cos_theta, &self.eta, &self.k);
+            return Some(BSDFSample::specular(scattered, weight, Lobe::NONE));
+        }
+        self.bsdf(r_in, rec).sample(r_in, rec, Lobe::GLOSSY)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        if self.smooth() {
+            return Color::default();
+        }
+        self.bsdf(r_in, rec).eval(&scattered.direction())
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        if self.smooth() {
+            return 0.0;
+        }
+        self.bsdf(r_in, rec).value(&scattered.direction())
+    }
+
+    fn lobes(&self) -> Lobe {
+        if self.smooth() {
+            Lobe::SPECULAR
+        } else {
+            Lobe::GLOSSY
+        }
+    }
+}
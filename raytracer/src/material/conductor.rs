@@ -0,0 +1,187 @@
+use crate::hittable::HitRecord;
+use crate::material::microfacet::*;
+use crate::material::thin_film::{FilmLayer, ThinFilm};
+use crate::material::{shading_frame, BSDFSample, Lobe, Material, BSDF};
+use crate::pdf::onb::ONB;
+use crate::pdf::PDF;
+use crate::utility::ray::Ray;
+use crate::utility::vec3::*;
+
+//rough metal, eta and k are the complex index of refraction at the red, green and blue wavelengths
+#[derive(Clone)]
+pub struct Conductor {
+    pub eta: Color,
+    pub k: Color,
+    pub roughness: f64, //0 is a perfect mirror
+    pub distribution: Distribution,
+    pub film: Option<ThinFilm>, //oxide or oil on top, tinting the reflection
+}
+
+impl Conductor {
+    pub fn new(eta: &Color, k: &Color, roughness: f64) -> Self {
+        Self {
+            eta: *eta,
+            k: *k,
+            roughness,
+            distribution: Distribution::GGX,
+            film: None,
+        }
+    }
+
+    pub fn with_film(mut self, film: ThinFilm) -> Self {
+        self.film = Some(film);
+        self
+    }
+
+    //measured n and k sampled at 650nm, 550nm and 450nm
+    pub fn gold() -> Self {
+        Self::new(
+            &Color::new(0.143, 0.374, 1.442),
+            &Color::new(3.983, 2.385, 1.603),
+            0.2,
+        )
+    }
+
+    pub fn silver() -> Self {
+        Self::new(
+            &Color::new(0.155, 0.117, 0.138),
+            &Color::new(4.828, 3.122, 2.147),
+            0.2,
+        )
+    }
+
+    pub fn copper() -> Self {
+        Self::new(
+            &Color::new(0.200, 0.924, 1.102),
+            &Color::new(3.912, 2.452, 2.142),
+            0.2,
+        )
+    }
+
+    pub fn aluminium() -> Self {
+        Self::new(
+            &Color::new(1.657, 0.880, 0.521),
+            &Color::new(9.224, 6.270, 4.837),
+            0.2,
+        )
+    }
+
+    fn smooth(&self) -> bool {
+        self.roughness * self.roughness < 1e-3
+    }
+
+    fn bsdf(&self, r_in: &Ray, rec: &HitRecord) -> ConductorBSDF {
+        let (uvw, _) = shading_frame(r_in, rec);
+        ConductorBSDF {
+            uvw,
+            wo: uvw.to_local(&(-r_in.direction().unit())),
+            microfacet: Microfacet::from_roughness(self.distribution, self.roughness),
+            eta: self.eta,
+            k: self.k,
+            film: self.film.as_ref().map(|film| film.at(rec)),
+        }
+    }
+}
+
+impl Material for Conductor {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BSDFSample> {
+        if self.smooth() {
+            //too sharp for light sampling to ever hit the lobe
+            let (uvw, _) = shading_frame(r_in, rec);
+            let unit_dir = r_in.direction().unit();
+            let cos_theta = dot(&(-unit_dir), &uvw.w());
+            let film = self.film.as_ref().map(|film| film.at(rec));
+            let scattered = Ray::new(&rec.p, &reflect(&unit_dir, &uvw.w()), r_in.time());
+            let weight = fresnel(cos_theta, &self.eta, &self.k, &film);
+            return Some(BSDFSample::specular(scattered, weight, Lobe::NONE));
+        }
+        self.bsdf(r_in, rec).sample(r_in, rec, Lobe::GLOSSY)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        if self.smooth() {
+            return Color::default();
+        }
+        self.bsdf(r_in, rec).eval(&scattered.direction())
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        if self.smooth() {
+            return 0.0;
+        }
+        self.bsdf(r_in, rec).value(&scattered.direction())
+    }
+
+    fn lobes(&self) -> Lobe {
+        if self.smooth() {
+            Lobe::SPECULAR
+        } else {
+            Lobe::GLOSSY
+        }
+    }
+}
+
+fn fresnel(cos_i: f64, eta: &Color, k: &Color, film: &Option<FilmLayer>) -> Color {
+    match film {
+        Some(film) => film.reflectance(cos_i, 1.0, eta, k),
+        None => fresnel_conductor(cos_i, eta, k),
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct ConductorBSDF {
+    uvw: ONB,
+    wo: Vec3,
+    microfacet: Microfacet,
+    eta: Color,
+    k: Color,
+    film: Option<FilmLayer>,
+}
+
+impl ConductorBSDF {
+    pub fn new(
+        uvw: ONB,
+        wo: Vec3,
+        microfacet: Microfacet,
+        eta: &Color,
+        k: &Color,
+        film: Option<FilmLayer>,
+    ) -> Self {
+        Self {
+            uvw,
+            wo,
+            microfacet,
+            eta: *eta,
+            k: *k,
+            film,
+        }
+    }
+}
+
+impl PDF for ConductorBSDF {
+    fn value(&self, direction: &Vec3) -> f64 {
+        let (wo, wi) = (self.wo, self.uvw.to_local(&direction.unit()));
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return 0.0;
+        }
+        let h = (wo + wi).unit();
+        self.microfacet.visible_d(&wo, &h) / (4.0 * dot(&wo, &h))
+    }
+
+    fn generate(&self) -> Vec3 {
+        let h = self.microfacet.sample_visible(&self.wo);
+        self.uvw.local_vec(&reflect_local(&self.wo, &h))
+    }
+}
+
+impl BSDF for ConductorBSDF {
+    fn eval(&self, direction: &Vec3) -> Color {
+        let (wo, wi) = (self.wo, self.uvw.to_local(&direction.unit()));
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Color::default();
+        }
+        let h = (wo + wi).unit();
+        let f = fresnel(dot(&wo, &h), &self.eta, &self.k, &self.film);
+        self.microfacet.d(&h) * self.microfacet.g(&wo, &wi) / (4.0 * wo.z()) * f
+    }
+}
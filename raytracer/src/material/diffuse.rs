@@ -0,0 +1,257 @@
+use crate::hittable::HitRecord;
+use crate::material::{shading_frame, BSDFSample, Lobe, Material};
+use crate::pdf::onb::ONB;
+use crate::pdf::{CosPDF, PDF};
+use crate::texture::{SolidColor, Texture};
+use crate::utility::random_double;
+use crate::utility::ray::Ray;
+use crate::utility::vec3::*;
+use std::f64::consts::PI;
+
+// Diffuse models beside Lambertian. Each one scales its albedo by a shape factor.
+
+//incoming and scattered directions in the shading frame, both pointing away from the surface
+fn local_directions(r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> (Vec3, Vec3) {
+    let (uvw, _) = shading_frame(r_in, rec);
+    (
+        uvw.to_local(&(-r_in.direction().unit())),
+        uvw.to_local(&scattered.direction().unit()),
+    )
+}
+
+//cosine weighted sample of a material whose eval is its albedo times a shape
+fn cosine_sample<M: Material>(mat: &M, r_in: &Ray, rec: &HitRecord) -> Option<BSDFSample> {
+    let (uvw, _) = shading_frame(r_in, rec);
+    let direction = CosPDF::new(&uvw.w()).generate();
+    let scattered = Ray::new(&rec.p, &direction, r_in.time());
+    let f = mat.eval(r_in, rec, &scattered);
+    BSDFSample::new(
+        scattered,
+        f,
+        cosine_pdf(r_in, rec, &scattered),
+        Lobe::DIFFUSE,
+    )
+}
+
+fn cosine_pdf(r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+    let (_, wi) = local_directions(r_in, rec, scattered);
+    wi.z().max(0.0) / PI
+}
+
+//rough clay, plaster or the moon: a surface of V-shaped Lambertian facets (Oren and Nayar 1994)
+#[derive(Clone, Default)]
+pub struct OrenNayar<T: Texture> {
+    pub albedo: T,
+    pub sigma: f64, //standard deviation of the facet slopes, in degrees
+}
+
+impl<T: Texture> OrenNayar<T> {
+    pub fn new(a: T, sigma: f64) -> Self {
+        Self { albedo: a, sigma }
+    }
+
+    //BRDF over albedo, times the cosine
+    fn shape(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return 0.0;
+        }
+        let sigma2 = self.sigma.to_radians().powi(2);
+        let a = 1.0 - sigma2 / (2.0 * (sigma2 + 0.33));
+        let b = 0.45 * sigma2 / (sigma2 + 0.09);
+
+        let sin_o = (1.0 - wo.z() * wo.z()).max(0.0).sqrt();
+        let sin_i = (1.0 - wi.z() * wi.z()).max(0.0).sqrt();
+        let mut max_cos = 0.0;
+        if sin_o > 1e-4 && sin_i > 1e-4 {
+            //cosine of the azimuth between the two directions
+            max_cos = ((wo.x() * wi.x() + wo.y() * wi.y()) / (sin_o * sin_i)).max(0.0);
+        }
+        let (sin_alpha, tan_beta) = if wi.z() > wo.z() {
+            (sin_o, sin_i / wi.z())
+        } else {
+            (sin_i, sin_o / wo.z())
+        };
+        (a + b * max_cos * sin_alpha * tan_beta) / PI * wi.z()
+    }
+}
+
+impl OrenNayar<SolidColor> {
+    pub fn new_from_color(color: &Color, sigma: f64) -> Self {
+        Self::new(SolidColor::new(color), sigma)
+    }
+}
+
+impl<T: Texture> Material for OrenNayar<T> {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BSDFSample> {
+        cosine_sample(self, r_in, rec)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let (wo, wi) = local_directions(r_in, rec, scattered);
+        self.albedo.value_at(rec) * self.shape(&wo, &wi)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        cosine_pdf(r_in, rec, scattered)
+    }
+
+    fn lobes(&self) -> Lobe {
+        Lobe::DIFFUSE
+    }
+}
+
+// Velvet and other cloth whose fibres stick out of the surface, brightest at grazing angles.
+// Charlie microfacet distribution (Estevez and Kulla 2017) with the visibility term of
+// Neubelt and Pettineo.
+#[derive(Clone, Default)]
+pub struct Sheen<T: Texture> {
+    pub albedo: T,
+    pub roughness: f64, //in (0, 1], how far the fibres lean away from the normal
+}
+
+impl<T: Texture> Sheen<T> {
+    pub fn new(a: T, roughness: f64) -> Self {
+        Self {
+            albedo: a,
+            roughness: roughness.max(0.01).min(1.0),
+        }
+    }
+
+    //BRDF over albedo, times the cosine
+    fn shape(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return 0.0;
+        }
+        let h = (*wo + *wi).unit();
+        let inv_r = 1.0 / self.roughness;
+        let sin_h = (1.0 - h.z() * h.z()).max(0.0).sqrt();
+        let d = (2.0 + inv_r) * sin_h.powf(inv_r) / (2.0 * PI);
+        let v = 1.0 / (4.0 * (wi.z() + wo.z() - wi.z() * wo.z()));
+        d * v * wi.z()
+    }
+}
+
+impl Sheen<SolidColor> {
+    pub fn new_from_color(color: &Color, roughness: f64) -> Self {
+        Self::new(SolidColor::new(color), roughness)
+    }
+}
+
+impl<T: Texture> Material for Sheen<T> {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BSDFSample> {
+        cosine_sample(self, r_in, rec)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let (wo, wi) = local_directions(r_in, rec, scattered);
+        self.albedo.value_at(rec) * self.shape(&wo, &wi)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        cosine_pdf(r_in, rec, scattered)
+    }
+
+    fn lobes(&self) -> Lobe {
+        Lobe::DIFFUSE
+    }
+}
+
+// Road signs and cat's eyes send light back where it came from.
+// A diffuse part plus a Phong lobe around the incoming direction.
+#[derive(Clone, Default)]
+pub struct RetroReflective<T: Texture> {
+    pub albedo: T,
+    pub retro: f64,    //fraction of the albedo reflected back
+    pub exponent: f64, //sharpness of the lobe around the incoming direction
+}
+
+impl<T: Texture> RetroReflective<T> {
+    pub fn new(a: T, retro: f64, exponent: f64) -> Self {
+        Self {
+            albedo: a,
+            retro: retro.max(0.0).min(1.0),
+            exponent: exponent.max(0.0),
+        }
+    }
+
+    fn lobe_pdf(&self, r_in: &Ray, rec: &HitRecord) -> RetroPDF {
+        let (uvw, _) = shading_frame(r_in, rec);
+        RetroPDF {
+            uvw,
+            lobe: ONB::build_from_w(&(-r_in.direction())),
+            retro: self.retro,
+            exponent: self.exponent,
+        }
+    }
+}
+
+impl RetroReflective<SolidColor> {
+    pub fn new_from_color(color: &Color, retro: f64, exponent: f64) -> Self {
+        Self::new(SolidColor::new(color), retro, exponent)
+    }
+}
+
+impl<T: Texture> Material for RetroReflective<T> {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BSDFSample> {
+        let pdf = self.lobe_pdf(r_in, rec);
+        let direction = pdf.generate();
+        let scattered = Ray::new(&rec.p, &direction, r_in.time());
+        let f = self.eval(r_in, rec, &scattered);
+        BSDFSample::new(
+            scattered,
+            f,
+            pdf.value(&direction),
+            Lobe::DIFFUSE | Lobe::GLOSSY,
+        )
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let (wo, wi) = local_directions(r_in, rec, scattered);
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Color::default();
+        }
+        //the lobe is taken per projected area so that signs stay bright at grazing angles
+        let n = self.exponent;
+        let lobe = (n + 1.0) / (2.0 * PI) * dot(&wo, &wi).max(0.0).powf(n);
+        self.albedo.value_at(rec) * ((1.0 - self.retro) / PI * wi.z() + self.retro * lobe)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.lobe_pdf(r_in, rec).value(&scattered.direction())
+    }
+
+    fn lobes(&self) -> Lobe {
+        Lobe::DIFFUSE | Lobe::GLOSSY
+    }
+}
+
+//cosine lobe for the diffuse part, Phong lobe around the incoming direction for the rest
+pub struct RetroPDF {
+    uvw: ONB,
+    lobe: ONB, //w points back along the incoming ray
+    retro: f64,
+    exponent: f64,
+}
+
+impl PDF for RetroPDF {
+    fn value(&self, direction: &Vec3) -> f64 {
+        let direction = direction.unit();
+        let cos = dot(&direction, &self.uvw.w());
+        if cos <= 0.0 {
+            return 0.0;
+        }
+        let n = self.exponent;
+        let cos_lobe = dot(&direction, &self.lobe.w()).max(0.0);
+        (1.0 - self.retro) * cos / PI + self.retro * (n + 1.0) / (2.0 * PI) * cos_lobe.powf(n)
+    }
+
+    fn generate(&self) -> Vec3 {
+        if random_double() >= self.retro {
+            return self.uvw.local_vec(&Vec3::random_cosine_direction());
+        }
+        let cos = random_double().powf(1.0 / (self.exponent + 1.0));
+        let sin = (1.0 - cos * cos).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_double();
+        self.lobe.local(sin * phi.cos(), sin * phi.sin(), cos)
+    }
+}
@@ -0,0 +1,362 @@
+use crate::hittable::HitRecord;
+use crate::material::microfacet::fresnel_dielectric;
+use crate::material::{BSDFSample, Lobe, Material, BSDF};
+use crate::pdf::onb::ONB;
+use crate::pdf::PDF;
+use crate::utility::random_double;
+use crate::utility::ray::Ray;
+use crate::utility::vec3::*;
+use std::f64::consts::{LN_2, PI};
+
+const P_MAX: usize = 3; //lobes R, TT and TRT, the rest is summed into a fourth one
+const SQRT_PI_OVER_8: f64 = 0.626_657_068_657_750_1;
+const EUMELANIN: (f64, f64, f64) = (0.419, 0.697, 1.37); //absorption per unit concentration
+const PHEOMELANIN: (f64, f64, f64) = (0.187, 0.4, 1.05);
+
+// Hair fibre as a rough dielectric cylinder with an absorbing interior (d'Eon et al. 2011,
+// Chiang et al. 2016, as in pbrt). Light reflects off the cuticle (R), passes through the fibre
+// (TT), or reflects once inside it (TRT). The scales of the cuticle tilt each lobe by alpha.
+// It is meant for flat curves, which give the offset across the fibre as v and the fibre
+// direction as the tangent.
+#[derive(Debug, Copy, Clone)]
+pub struct Hair {
+    pub sigma_a: Color, //absorption inside the fibre, per unit of its diameter
+    pub beta_m: f64,    //longitudinal roughness in [0, 1]
+    pub beta_n: f64,    //azimuthal roughness in [0, 1]
+    pub alpha: f64,     //tilt of the cuticle scales, in degrees
+    pub eta: f64,
+}
+
+impl Default for Hair {
+    fn default() -> Self {
+        Self::new(&Color::default(), 0.3, 0.3)
+    }
+}
+
+impl Hair {
+    pub fn new(sigma_a: &Color, beta_m: f64, beta_n: f64) -> Self {
+        Self {
+            sigma_a: *sigma_a,
+            beta_m: beta_m.max(0.01).min(1.0),
+            beta_n: beta_n.max(0.01).min(1.0),
+            alpha: 2.0,
+            eta: 1.55,
+        }
+    }
+
+    // Natural hair colours come from two pigments: eumelanin makes it black or brown, about 8
+    // for black, 1.3 for brown and 0.3 for blonde, and pheomelanin makes it red.
+    pub fn new_from_melanin(eumelanin: f64, pheomelanin: f64, beta_m: f64, beta_n: f64) -> Self {
+        let e = Color::new(EUMELANIN.0, EUMELANIN.1, EUMELANIN.2);
+        let p = Color::new(PHEOMELANIN.0, PHEOMELANIN.1, PHEOMELANIN.2);
+        Self::new(&(eumelanin * e + pheomelanin * p), beta_m, beta_n)
+    }
+
+    //absorption giving roughly this colour to a head of hair, for dyed hair and fur
+    pub fn new_from_color(color: &Color, beta_m: f64, beta_n: f64) -> Self {
+        let beta_n = beta_n.max(0.01).min(1.0);
+        let d = 5.969 - 0.215 * beta_n + 2.532 * beta_n.powi(2) - 10.73 * beta_n.powi(3)
+            + 5.574 * beta_n.powi(4)
+            + 0.245 * beta_n.powi(5);
+        let sigma = |c: f64| (c.max(1e-4).ln() / d).powi(2);
+        let sigma_a = Color::new(sigma(color.x()), sigma(color.y()), sigma(color.z()));
+        Self::new(&sigma_a, beta_m, beta_n)
+    }
+
+    fn bsdf(&self, r_in: &Ray, rec: &HitRecord) -> HairBSDF {
+        //x along the fibre, y across it and z towards the viewer
+        let uvw = ONB::build_from_wu(&rec.geometric_normal, &rec.tangent);
+        let wo = uvw.to_local(&(-r_in.direction().unit()));
+        HairBSDF::new(uvw, wo, self, (2.0 * rec.v - 1.0).max(-1.0).min(1.0))
+    }
+}
+
+impl Material for Hair {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BSDFSample> {
+        self.bsdf(r_in, rec).sample(r_in, rec, Lobe::GLOSSY)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.bsdf(r_in, rec).eval(&scattered.direction())
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.bsdf(r_in, rec).value(&scattered.direction())
+    }
+
+    fn lobes(&self) -> Lobe {
+        Lobe::GLOSSY | Lobe::TRANSMISSION
+    }
+}
+
+//the scattering at one hit, in the frame of the fibre
+pub struct HairBSDF {
+    uvw: ONB,
+    wo: Vec3,
+    h: f64, //offset of the hit across the fibre, in [-1, 1]
+    gamma_o: f64,
+    eta: f64,
+    sigma_a: Color,
+    v: [f64; P_MAX + 1], //variance of each longitudinal lobe
+    s: f64,              //scale of the azimuthal logistic
+    sin_2k_alpha: [f64; 3],
+    cos_2k_alpha: [f64; 3],
+}
+
+impl HairBSDF {
+    pub fn new(uvw: ONB, wo: Vec3, hair: &Hair, h: f64) -> Self {
+        let bm = hair.beta_m;
+        let bn = hair.beta_n;
+        let v0 = (0.726 * bm + 0.812 * bm * bm + 3.7 * bm.powi(20)).powi(2);
+        let mut sin_2k_alpha = [hair.alpha.to_radians().sin(), 0.0, 0.0];
+        let mut cos_2k_alpha = [(1.0 - sin_2k_alpha[0].powi(2)).max(0.0).sqrt(), 0.0, 0.0];
+        for i in 1..3 {
+            sin_2k_alpha[i] = 2.0 * cos_2k_alpha[i - 1] * sin_2k_alpha[i - 1];
+            cos_2k_alpha[i] = cos_2k_alpha[i - 1].powi(2) - sin_2k_alpha[i - 1].powi(2);
+        }
+        Self {
+            uvw,
+            wo,
+            h,
+            gamma_o: h.asin(),
+            eta: hair.eta,
+            sigma_a: hair.sigma_a,
+            v: [v0, 0.25 * v0, 4.0 * v0, 4.0 * v0],
+            s: SQRT_PI_OVER_8 * (0.265 * bn + 1.194 * bn * bn + 5.372 * bn.powi(22)),
+            sin_2k_alpha,
+            cos_2k_alpha,
+        }
+    }
+
+    // Attenuation of each lobe and the refracted azimuth, for light leaving at this sine of the
+    // longitudinal angle.
+    fn attenuations(&self, sin_theta_o: f64) -> ([Color; P_MAX + 1], f64) {
+        let cos_theta_o = (1.0 - sin_theta_o * sin_theta_o).max(0.0).sqrt();
+        let sin_theta_t = sin_theta_o / self.eta;
+        let cos_theta_t = (1.0 - sin_theta_t * sin_theta_t).max(0.0).sqrt();
+        //the index seen in the plane across the fibre (Bravais)
+        let etap = (self.eta * self.eta - sin_theta_o * sin_theta_o).sqrt() / cos_theta_o.max(1e-8);
+        let sin_gamma_t = (self.h / etap).max(-1.0).min(1.0);
+        let cos_gamma_t = (1.0 - sin_gamma_t * sin_gamma_t).max(0.0).sqrt();
+        let gamma_t = sin_gamma_t.asin();
+
+        //transmittance of one pass through the fibre
+        let length = 2.0 * cos_gamma_t / cos_theta_t.max(1e-8);
+        let t = Color::new(
+            (-self.sigma_a.x() * length).exp(),
+            (-self.sigma_a.y() * length).exp(),
+            (-self.sigma_a.z() * length).exp(),
+        );
+
+        let cos_gamma_o = (1.0 - self.h * self.h).max(0.0).sqrt();
+        let f = fresnel_dielectric(cos_theta_o * cos_gamma_o, self.eta);
+        let mut ap = [Color::default(); P_MAX + 1];
+        ap[0] = Color::same(f);
+        ap[1] = (1.0 - f) * (1.0 - f) * t;
+        ap[2] = ap[1] * t * f;
+        let tf = t * f;
+        ap[3] = Color::new(
+            ap[2].x() * tf.x() / (1.0 - tf.x()),
+            ap[2].y() * tf.y() / (1.0 - tf.y()),
+            ap[2].z() * tf.z() / (1.0 - tf.z()),
+        );
+        (ap, gamma_t)
+    }
+
+    //the outgoing angle tilted by the cuticle scales for lobe p
+    fn tilted(&self, p: usize, sin_theta_o: f64, cos_theta_o: f64) -> (f64, f64) {
+        let (sin, cos) = (self.sin_2k_alpha, self.cos_2k_alpha);
+        let (s, c) = match p {
+            0 => (
+                sin_theta_o * cos[1] - cos_theta_o * sin[1],
+                cos_theta_o * cos[1] + sin_theta_o * sin[1],
+            ),
+            1 => (
+                sin_theta_o * cos[0] + cos_theta_o * sin[0],
+                cos_theta_o * cos[0] - sin_theta_o * sin[0],
+            ),
+            2 => (
+                sin_theta_o * cos[2] + cos_theta_o * sin[2],
+                cos_theta_o * cos[2] - sin_theta_o * sin[2],
+            ),
+            _ => (sin_theta_o, cos_theta_o),
+        };
+        (s, c.abs())
+    }
+
+    //longitudinal times azimuthal scattering of lobe p
+    fn lobe(&self, p: usize, wi: &Vec3, gamma_t: f64) -> f64 {
+        let sin_theta_o = self.wo.x();
+        let cos_theta_o = (1.0 - sin_theta_o * sin_theta_o).max(0.0).sqrt();
+        let sin_theta_i = wi.x();
+        let cos_theta_i = (1.0 - sin_theta_i * sin_theta_i).max(0.0).sqrt();
+        let phi = wi.z().atan2(wi.y()) - self.wo.z().atan2(self.wo.y());
+        let (sin_theta_op, cos_theta_op) = self.tilted(p, sin_theta_o, cos_theta_o);
+        let mp = longitudinal(
+            cos_theta_i,
+            cos_theta_op,
+            sin_theta_i,
+            sin_theta_op,
+            self.v[p],
+        );
+        if p < P_MAX {
+            mp * self.azimuthal(phi, p, gamma_t)
+        } else {
+            mp / (2.0 * PI)
+        }
+    }
+
+    fn azimuthal(&self, phi: f64, p: usize, gamma_t: f64) -> f64 {
+        let mut dphi = phi - self.phi(p, gamma_t);
+        while dphi > PI {
+            dphi -= 2.0 * PI;
+        }
+        while dphi < -PI {
+            dphi += 2.0 * PI;
+        }
+        trimmed_logistic(dphi, self.s, -PI, PI)
+    }
+
+    //azimuth of the light leaving lobe p
+    fn phi(&self, p: usize, gamma_t: f64) -> f64 {
+        let p = p as f64;
+        2.0 * p * gamma_t - 2.0 * self.gamma_o + p * PI
+    }
+
+    //probability of sampling each lobe, by its attenuation
+    fn lobe_pdf(&self) -> ([f64; P_MAX + 1], f64) {
+        let (ap, gamma_t) = self.attenuations(self.wo.x());
+        let mut pdf = [0.0; P_MAX + 1];
+        for (i, a) in ap.iter().enumerate() {
+            pdf[i] = (a.x() + a.y() + a.z()) / 3.0;
+        }
+        let sum: f64 = pdf.iter().sum();
+        if sum > 0.0 {
+            for p in pdf.iter_mut() {
+                *p /= sum;
+            }
+        }
+        (pdf, gamma_t)
+    }
+}
+
+impl PDF for HairBSDF {
+    fn value(&self, direction: &Vec3) -> f64 {
+        let wi = self.uvw.to_local(&direction.unit());
+        let (ap_pdf, gamma_t) = self.lobe_pdf();
+        ap_pdf
+            .iter()
+            .enumerate()
+            .map(|(p, pdf)| pdf * self.lobe(p, &wi, gamma_t))
+            .sum()
+    }
+
+    fn generate(&self) -> Vec3 {
+        let (ap_pdf, gamma_t) = self.lobe_pdf();
+        let mut u = random_double();
+        let mut p = 0;
+        while p < P_MAX && u >= ap_pdf[p] {
+            u -= ap_pdf[p];
+            p += 1;
+        }
+
+        //longitudinal angle around the tilted mirror direction
+        let sin_theta_o = self.wo.x();
+        let cos_theta_o = (1.0 - sin_theta_o * sin_theta_o).max(0.0).sqrt();
+        let (sin_theta_op, cos_theta_op) = self.tilted(p, sin_theta_o, cos_theta_o);
+        let v = self.v[p];
+        let u1 = random_double().max(1e-5);
+        let cos_theta = 1.0 + v * (u1 + (1.0 - u1) * (-2.0 / v).exp()).ln();
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let cos_phi = (2.0 * PI * random_double()).cos();
+        let sin_theta_i = -cos_theta * sin_theta_op + sin_theta * cos_phi * cos_theta_op;
+        let cos_theta_i = (1.0 - sin_theta_i * sin_theta_i).max(0.0).sqrt();
+
+        let dphi = if p < P_MAX {
+            self.phi(p, gamma_t) + sample_trimmed_logistic(random_double(), self.s, -PI, PI)
+        } else {
+            2.0 * PI * random_double()
+        };
+        let phi_i = self.wo.z().atan2(self.wo.y()) + dphi;
+        self.uvw.local(
+            sin_theta_i,
+            cos_theta_i * phi_i.cos(),
+            cos_theta_i * phi_i.sin(),
+        )
+    }
+}
+
+impl BSDF for HairBSDF {
+    fn eval(&self, direction: &Vec3) -> Color {
+        let wi = self.uvw.to_local(&direction.unit());
+        let (ap, gamma_t) = self.attenuations(self.wo.x());
+        let mut sum = Color::default();
+        for (p, ap) in ap.iter().enumerate() {
+            sum += *ap * self.lobe(p, &wi, gamma_t);
+        }
+        sum
+    }
+}
+
+//modified Bessel function of the first kind, of order 0
+fn bessel_i0(x: f64) -> f64 {
+    let mut val = 0.0;
+    let mut x2i = 1.0;
+    let mut factorial = 1.0;
+    let mut four_i = 1.0;
+    for i in 0..10 {
+        if i > 1 {
+            factorial *= i as f64;
+        }
+        val += x2i / (four_i * factorial * factorial);
+        x2i *= x * x;
+        four_i *= 4.0;
+    }
+    val
+}
+
+fn log_bessel_i0(x: f64) -> f64 {
+    if x > 12.0 {
+        x + 0.5 * (-(2.0 * PI).ln() + (1.0 / x).ln() + 1.0 / (8.0 * x))
+    } else {
+        bessel_i0(x).ln()
+    }
+}
+
+//longitudinal scattering of variance v, in a form that stays finite for low roughness
+fn longitudinal(
+    cos_theta_i: f64,
+    cos_theta_o: f64,
+    sin_theta_i: f64,
+    sin_theta_o: f64,
+    v: f64,
+) -> f64 {
+    let a = cos_theta_i * cos_theta_o / v;
+    let b = sin_theta_i * sin_theta_o / v;
+    if v <= 0.1 {
+        (log_bessel_i0(a) - b - 1.0 / v + LN_2 + (1.0 / (2.0 * v)).ln()).exp()
+    } else {
+        (-b).exp() * bessel_i0(a) / ((1.0 / v).sinh() * 2.0 * v)
+    }
+}
+
+fn logistic(x: f64, s: f64) -> f64 {
+    let x = x.abs();
+    let e = (-x / s).exp();
+    e / (s * (1.0 + e) * (1.0 + e))
+}
+
+fn logistic_cdf(x: f64, s: f64) -> f64 {
+    1.0 / (1.0 + (-x / s).exp())
+}
+
+//logistic distribution restricted to [a, b]
+fn trimmed_logistic(x: f64, s: f64, a: f64, b: f64) -> f64 {
+    logistic(x, s) / (logistic_cdf(b, s) - logistic_cdf(a, s))
+}
+
+fn sample_trimmed_logistic(u: f64, s: f64, a: f64, b: f64) -> f64 {
+    let k = logistic_cdf(b, s) - logistic_cdf(a, s);
+    let x = -s * (1.0 / (u * k + logistic_cdf(a, s)) - 1.0).ln();
+    x.max(a).min(b)
+}
@@ -0,0 +1,207 @@
+use crate::hittable::HitRecord;
+use crate::material::microfacet::fresnel_dielectric;
+use crate::material::{shading_frame, BSDFSample, Lobe, Material};
+use crate::pdf::onb::ONB;
+use crate::utility::random_double;
+use crate::utility::ray::Ray;
+use crate::utility::vec3::*;
+
+const MAX_COAT_BOUNCES: usize = 16;
+
+// A smooth dielectric coat over any base material, like car paint or lacquer.
+// The coat reflects with its Fresnel probability, otherwise the ray is refracted in and
+// bounces between the base and the coat until it refracts out again. Light below the coat
+// that cannot be sampled in closed form is estimated with random walks, in eval as well.
+#[derive(Clone)]
+pub struct Layered<M: Material> {
+    pub base: M,
+    pub ior: f64,
+    pub thickness: f64,
+    pub absorption: Color, //absorption coefficient of the coat per unit length
+}
+
+impl<M: Material> Layered<M> {
+    pub fn new(base: M, ior: f64, thickness: f64, absorption: &Color) -> Self {
+        Self {
+            base,
+            ior,
+            thickness,
+            absorption: *absorption,
+        }
+    }
+
+    //clear coat without absorption
+    pub fn clear(base: M, ior: f64) -> Self {
+        Self::new(base, ior, 0.0, &Color::default())
+    }
+
+    //absorption along one way through the coat
+    fn transmittance(&self, cos: f64) -> Color {
+        let length = self.thickness / cos.max(1e-4);
+        Color::new(
+            (-self.absorption.x() * length).exp(),
+            (-self.absorption.y() * length).exp(),
+            (-self.absorption.z() * length).exp(),
+        )
+    }
+
+    // Share of the light leaving the base towards wi_coat that gets out towards wi. Radiance
+    // gains eta^2 entering and loses it leaving, and the solid angle compression
+    // cos / (eta^2 cos') turns the base's cosine into the one outside.
+    fn exit(&self, wi: &Vec3, wi_coat: &Vec3) -> f64 {
+        (1.0 - fresnel_dielectric(wi.z(), self.ior)) * wi.z() / (self.ior * self.ior * wi_coat.z())
+    }
+
+    //the incoming ray as the base first sees it below the coat
+    fn coat_ray(&self, r_in: &Ray, rec: &HitRecord, uvw: &ONB) -> Ray {
+        let wo = uvw.to_local(&(-r_in.direction().unit()));
+        let wo_coat = refract_in(&wo, self.ior);
+        Ray::new(&rec.p, &(-uvw.local_vec(&wo_coat)), r_in.time())
+    }
+}
+
+impl<M: Material> Material for Layered<M> {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BSDFSample> {
+        let (uvw, entering) = shading_frame(r_in, rec);
+        if !entering {
+            return self.base.sample(r_in, rec);
+        }
+        let unit_dir = r_in.direction().unit();
+        if random_double() < fresnel_dielectric(dot(&unit_dir, &uvw.w()), self.ior) {
+            let scattered = Ray::new(&rec.p, &reflect(&unit_dir, &uvw.w()), r_in.time());
+            return Some(BSDFSample::specular(
+                scattered,
+                Color::same(1.0),
+                Lobe::NONE,
+            ));
+        }
+
+        // Random walk between the base and the underside of the coat. Each time the light
+        // comes up from the base, the coat lets it out or reflects it back down with its
+        // Fresnel probability. The exit is specular only if every bounce on the base was.
+        let mut wo_coat = refract_in(&uvw.to_local(&(-unit_dir)), self.ior);
+        let mut weight = Color::same(1.0);
+        let mut lobe = Lobe::NONE;
+        for _ in 0..MAX_COAT_BOUNCES {
+            let coat_ray = Ray::new(&rec.p, &(-uvw.local_vec(&wo_coat)), r_in.time());
+            weight = weight * self.transmittance(wo_coat.z());
+            let sample = self.base.sample(&coat_ray, rec)?;
+            let wi_coat = uvw.to_local(&sample.scattered.direction().unit());
+            if wi_coat.z() <= 0.0 {
+                return None;
+            }
+            weight = weight * sample.weight * self.transmittance(wi_coat.z());
+            if !sample.lobe.is_specular() {
+                lobe = lobe | sample.lobe;
+            }
+            //refraction out always exists when the coat does not reflect totally
+            if random_double() >= fresnel_dielectric(wi_coat.z(), 1.0 / self.ior) {
+                let wi = refract_out(&wi_coat, self.ior)?;
+                let scattered = Ray::new(&rec.p, &uvw.local_vec(&wi), r_in.time());
+                if !lobe.non_specular() {
+                    return Some(BSDFSample::specular(scattered, weight, Lobe::NONE));
+                }
+                let pdf = self.pdf(r_in, rec, &scattered);
+                return Some(BSDFSample {
+                    scattered,
+                    weight,
+                    pdf,
+                    lobe,
+                });
+            }
+            wo_coat = Vec3::new(-wi_coat.x(), -wi_coat.y(), wi_coat.z());
+        }
+        None
+    }
+
+    // Light reaching the direction from every bounce of a random walk like sample's, an
+    // estimate of the BSDF that is right on average.
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let (uvw, entering) = shading_frame(r_in, rec);
+        if !entering {
+            return self.base.eval(r_in, rec, scattered);
+        }
+        let wi = uvw.to_local(&scattered.direction().unit());
+        if wi.z() <= 0.0 {
+            return Color::default();
+        }
+        let wo = uvw.to_local(&(-r_in.direction().unit()));
+        let wi_coat = refract_in(&wi, self.ior);
+        let scattered_coat = Ray::new(&rec.p, &uvw.local_vec(&wi_coat), scattered.time());
+
+        let mut wo_coat = refract_in(&wo, self.ior);
+        let mut weight = Color::same(1.0);
+        let mut base = Color::default();
+        for _ in 0..MAX_COAT_BOUNCES {
+            let coat_ray = Ray::new(&rec.p, &(-uvw.local_vec(&wo_coat)), r_in.time());
+            weight = weight * self.transmittance(wo_coat.z());
+            base += weight * self.base.eval(&coat_ray, rec, &scattered_coat);
+
+            //the part the coat reflects back down goes on, with russian roulette
+            let sample = match self.base.sample(&coat_ray, rec) {
+                Some(sample) => sample,
+                None => break,
+            };
+            let w = uvw.to_local(&sample.scattered.direction().unit());
+            if w.z() <= 0.0 {
+                break;
+            }
+            let fr = fresnel_dielectric(w.z(), 1.0 / self.ior);
+            weight = weight * sample.weight * self.transmittance(w.z()) * fr;
+            let q = weight.x().max(weight.y()).max(weight.z()).min(1.0);
+            if random_double() >= q {
+                break;
+            }
+            weight /= q;
+            wo_coat = Vec3::new(-w.x(), -w.y(), w.z());
+        }
+        let entry = 1.0 - fresnel_dielectric(wo.z(), self.ior);
+        entry * self.exit(&wi, &wi_coat) * self.transmittance(wi_coat.z()) * base
+    }
+
+    //the base's pdf of the first bounce seen through the coat, for weighting light samples
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let (uvw, entering) = shading_frame(r_in, rec);
+        if !entering {
+            return self.base.pdf(r_in, rec, scattered);
+        }
+        let wi = uvw.to_local(&scattered.direction().unit());
+        if wi.z() <= 0.0 {
+            return 0.0;
+        }
+        let wo = uvw.to_local(&(-r_in.direction().unit()));
+        let wi_coat = refract_in(&wi, self.ior);
+        let scattered_coat = Ray::new(&rec.p, &uvw.local_vec(&wi_coat), scattered.time());
+        let coat_ray = self.coat_ray(r_in, rec, &uvw);
+        //solid angle shrinks by cos / (eta^2 cos') when refracting in
+        (1.0 - fresnel_dielectric(wo.z(), self.ior))
+            * self.base.pdf(&coat_ray, rec, &scattered_coat)
+            * wi.z()
+            / (self.ior * self.ior * wi_coat.z())
+    }
+
+    fn lobes(&self) -> Lobe {
+        self.base.lobes() | Lobe::SPECULAR
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord, u: f64, v: f64, p: &Point3) -> Color {
+        self.base.emitted(r_in, rec, u, v, p)
+    }
+}
+
+// Directions in the local frame, pointing away from the surface on both sides of the coat.
+fn refract_in(w: &Vec3, ior: f64) -> Vec3 {
+    let (x, y) = (w.x() / ior, w.y() / ior);
+    Vec3::new(x, y, (1.0 - x * x - y * y).max(0.0).sqrt())
+}
+
+//None when the direction is totally reflected by the coat
+fn refract_out(w: &Vec3, ior: f64) -> Option<Vec3> {
+    let (x, y) = (w.x() * ior, w.y() * ior);
+    let sin2 = x * x + y * y;
+    if w.z() <= 0.0 || sin2 >= 1.0 {
+        None
+    } else {
+        Some(Vec3::new(x, y, (1.0 - sin2).sqrt()))
+    }
+}
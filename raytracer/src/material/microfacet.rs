@@ -0,0 +1,373 @@
+use crate::utility::random_double;
+use crate::utility::vec3::*;
+use std::f64::consts::PI;
+use std::f64::INFINITY;
+
+// All directions are in the local shading frame, the normal is +z.
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Distribution {
+    GGX, //Trowbridge-Reitz, long tails
+    Beckmann,
+}
+
+//microfacet normal distribution with the separable Smith shadowing term
+//alpha_x and alpha_y are the roughness along the x and y axes of the shading frame
+#[derive(Debug, Copy, Clone)]
+pub struct Microfacet {
+    pub distribution: Distribution,
+    pub alpha_x: f64,
+    pub alpha_y: f64,
+}
+
+impl Microfacet {
+    pub fn new(distribution: Distribution, alpha: f64) -> Self {
+        Self::anisotropic(distribution, alpha, alpha)
+    }
+
+    pub fn anisotropic(distribution: Distribution, alpha_x: f64, alpha_y: f64) -> Self {
+        Self {
+            distribution,
+            alpha_x: alpha_x.max(1e-3),
+            alpha_y: alpha_y.max(1e-3),
+        }
+    }
+
+    //the larger of the two roughnesses, to tell near specular lobes
+    pub fn max_alpha(&self) -> f64 {
+        self.alpha_x.max(self.alpha_y)
+    }
+
+    //squared slope of w scaled by the roughness in its azimuth, alpha^2 tan^2
+    fn stretched_tan2(&self, w: &Vec3) -> f64 {
+        let (x, y) = (self.alpha_x * w.x(), self.alpha_y * w.y());
+        (x * x + y * y) / (w.z() * w.z())
+    }
+
+    pub fn ggx(alpha: f64) -> Self {
+        Self::new(Distribution::GGX, alpha)
+    }
+
+    pub fn from_roughness(distribution: Distribution, roughness: f64) -> Self {
+        Self::new(distribution, roughness * roughness)
+    }
+
+    pub fn d(&self, h: &Vec3) -> f64 {
+        if h.z() <= 0.0 {
+            return 0.0;
+        }
+        let (ax, ay) = (self.alpha_x, self.alpha_y);
+        let (x, y) = (h.x() / ax, h.y() / ay);
+        let cos2 = h.z() * h.z();
+        match self.distribution {
+            Distribution::GGX => {
+                let t = x * x + y * y + cos2;
+                1.0 / (PI * ax * ay * t * t)
+            }
+            Distribution::Beckmann => {
+                (-(x * x + y * y) / cos2).exp() / (PI * ax * ay * cos2 * cos2)
+            }
+        }
+    }
+
+    pub fn lambda(&self, w: &Vec3) -> f64 {
+        if w.z() == 0.0 {
+            return INFINITY;
+        }
+        let a2_tan2 = self.stretched_tan2(w);
+        match self.distribution {
+            Distribution::GGX => ((1.0 + a2_tan2).sqrt() - 1.0) / 2.0,
+            Distribution::Beckmann => {
+                //rational fit of the exact erfc form
+                let a = 1.0 / a2_tan2.sqrt();
+                if a >= 1.6 {
+                    0.0
+                } else {
+                    (1.0 - 1.259 * a + 0.396 * a * a) / (3.535 * a + 2.181 * a * a)
+                }
+            }
+        }
+    }
+
+    pub fn g1(&self, w: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    pub fn g(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        self.g1(wo) * self.g1(wi)
+    }
+
+    //distribution of the normals visible from wo (wo.z > 0)
+    pub fn visible_d(&self, wo: &Vec3, h: &Vec3) -> f64 {
+        if wo.z() <= 0.0 {
+            return 0.0;
+        }
+        self.g1(wo) * dot(wo, h).max(0.0) * self.d(h) / wo.z()
+    }
+
+    pub fn sample_visible(&self, wo: &Vec3) -> Vec3 {
+        match self.distribution {
+            Distribution::GGX => self.sample_visible_ggx(wo),
+            Distribution::Beckmann => self.sample_visible_beckmann(wo),
+        }
+    }
+
+    //rough dielectric interface (Walter et al. 2007), eta = inside over outside on the side of wo
+    //returns the BSDF times the cosine of wi
+    pub fn dielectric_eval(&self, wo: &Vec3, wi: &Vec3, eta: f64) -> f64 {
+        if wo.z() <= 0.0 || wi.z() == 0.0 {
+            return 0.0;
+        }
+        if wi.z() > 0.0 {
+            let h = (*wo + *wi).unit();
+            let fr = fresnel_dielectric(dot(wo, &h), eta);
+            return fr * self.d(&h) * self.g(wo, wi) / (4.0 * wo.z());
+        }
+        let h = refraction_half_vector(wo, wi, eta);
+        let (cos_oh, cos_ih) = (dot(wo, &h), dot(wi, &h));
+        if cos_oh <= 0.0 || cos_ih >= 0.0 {
+            return 0.0;
+        }
+        let denom = cos_ih + cos_oh / eta;
+        let ft = 1.0 - fresnel_dielectric(cos_oh, eta);
+        //radiance is compressed into the denser medium
+        self.d(&h) * self.g(wo, wi) * ft * (cos_ih * cos_oh / (wo.z() * denom * denom)).abs()
+            / (eta * eta)
+    }
+
+    pub fn dielectric_pdf(&self, wo: &Vec3, wi: &Vec3, eta: f64) -> f64 {
+        if wo.z() <= 0.0 || wi.z() == 0.0 {
+            return 0.0;
+        }
+        if wi.z() > 0.0 {
+            let h = (*wo + *wi).unit();
+            let cos_oh = dot(wo, &h);
+            if cos_oh <= 0.0 {
+                return 0.0;
+            }
+            return fresnel_dielectric(cos_oh, eta) * self.visible_d(wo, &h) / (4.0 * cos_oh);
+        }
+        let h = refraction_half_vector(wo, wi, eta);
+        let (cos_oh, cos_ih) = (dot(wo, &h), dot(wi, &h));
+        if cos_oh <= 0.0 || cos_ih >= 0.0 {
+            return 0.0;
+        }
+        let denom = cos_ih + cos_oh / eta;
+        let ft = 1.0 - fresnel_dielectric(cos_oh, eta);
+        ft * self.visible_d(wo, &h) * cos_ih.abs() / (denom * denom)
+    }
+
+    //reflect or refract on a visible normal, chosen by its Fresnel reflectance
+    pub fn sample_dielectric(&self, wo: &Vec3, eta: f64) -> Vec3 {
+        let h = self.sample_visible(wo);
+        if random_double() < fresnel_dielectric(dot(wo, &h), eta) {
+            reflect_local(wo, &h)
+        } else {
+            refract_local(wo, &h, eta).unwrap_or_else(|| reflect_local(wo, &h))
+        }
+    }
+
+    //Heitz 2018, "Sampling the GGX Distribution of Visible Normals"
+    fn sample_visible_ggx(&self, wo: &Vec3) -> Vec3 {
+        let (ax, ay) = (self.alpha_x, self.alpha_y);
+        let vh = Vec3::new(ax * wo.x(), ay * wo.y(), wo.z()).unit();
+        let lensq = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if lensq > 0.0 {
+            Vec3::new(-vh.y(), vh.x(), 0.0) / lensq.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = cross(&vh, &t1);
+        let r = random_double().sqrt();
+        let phi = 2.0 * PI * random_double();
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+        Vec3::new(ax * nh.x(), ay * nh.y(), nh.z().max(1e-6)).unit()
+    }
+
+    //Heitz and d'Eon 2014, slopes of the visible normals in the stretched configuration
+    fn sample_visible_beckmann(&self, wo: &Vec3) -> Vec3 {
+        let (ax, ay) = (self.alpha_x, self.alpha_y);
+        let stretched = Vec3::new(ax * wo.x(), ay * wo.y(), wo.z()).unit();
+        let (u1, u2) = (random_double(), random_double());
+        let cos_theta = stretched.z();
+        let (mut slope_x, mut slope_y) = if cos_theta > 0.9999 {
+            let r = (-(1.0 - u1).ln()).sqrt();
+            let phi = 2.0 * PI * u2;
+            (r * phi.cos(), r * phi.sin())
+        } else {
+            let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+            let tan_theta = sin_theta / cos_theta;
+            let cot_theta = 1.0 / tan_theta;
+            let sqrt_pi_inv = 1.0 / PI.sqrt();
+
+            //invert the cdf of slope_x with a few Newton-bisection steps
+            let mut a = -1.0;
+            let mut c = erf(cot_theta);
+            let x = u1.max(1e-6);
+            let theta = cos_theta.acos();
+            let fit = 1.0 + theta * (-0.876 + theta * (0.4265 - 0.0594 * theta));
+            let mut b = c - (1.0 + c) * (1.0 - x).powf(fit);
+            let normalization =
+                1.0 / (1.0 + c + sqrt_pi_inv * tan_theta * (-cot_theta * cot_theta).exp());
+            for _ in 0..9 {
+                if !(b >= a && b <= c) {
+                    b = 0.5 * (a + c);
+                }
+                let inv_erf = erf_inv(b);
+                let value = normalization
+                    * (1.0 + b + sqrt_pi_inv * tan_theta * (-inv_erf * inv_erf).exp())
+                    - x;
+                let derivative = normalization * (1.0 - inv_erf * tan_theta);
+                if value.abs() < 1e-5 {
+                    break;
+                }
+                if value > 0.0 {
+                    c = b;
+                } else {
+                    a = b;
+                }
+                b -= value / derivative;
+            }
+            (erf_inv(b), erf_inv(2.0 * u2.max(1e-6) - 1.0))
+        };
+
+        //rotate to the azimuth of wo and unstretch
+        let len = (stretched.x() * stretched.x() + stretched.y() * stretched.y()).sqrt();
+        let (cos_phi, sin_phi) = if len > 0.0 {
+            (stretched.x() / len, stretched.y() / len)
+        } else {
+            (1.0, 0.0)
+        };
+        let tmp = cos_phi * slope_x - sin_phi * slope_y;
+        slope_y = sin_phi * slope_x + cos_phi * slope_y;
+        slope_x = tmp;
+        Vec3::new(-ax * slope_x, -ay * slope_y, 1.0).unit()
+    }
+}
+
+//Abramowitz and Stegun 7.1.26
+fn erf(x: f64) -> f64 {
+    let (a1, a2, a3, a4, a5, p) = (
+        0.254829592,
+        -0.284496736,
+        1.421413741,
+        -1.453152027,
+        1.061405429,
+        0.3275911,
+    );
+    let sign = x.signum();
+    let x = x.abs();
+    let t = 1.0 / (1.0 + p * x);
+    let y = 1.0 - (((((a5 * t + a4) * t) + a3) * t + a2) * t + a1) * t * (-x * x).exp();
+    sign * y
+}
+
+//Giles 2010, "Approximating the erfinv function"
+fn erf_inv(x: f64) -> f64 {
+    let x = x.max(-0.99999).min(0.99999);
+    let mut w = -((1.0 - x) * (1.0 + x)).ln();
+    let p = if w < 5.0 {
+        w -= 2.5;
+        [
+            3.43273939e-07,
+            -3.5233877e-06,
+            -4.39150654e-06,
+            0.00021858087,
+            -0.00125372503,
+            -0.00417768164,
+            0.246640727,
+            1.50140941,
+        ]
+        .iter()
+        .fold(2.81022636e-08, |p, c| c + p * w)
+    } else {
+        w = w.sqrt() - 3.0;
+        [
+            0.000100950558,
+            0.00134934322,
+            -0.00367342844,
+            0.00573950773,
+            -0.0076224613,
+            0.00943887047,
+            1.00167406,
+            2.83297682,
+        ]
+        .iter()
+        .fold(-0.000200214257, |p, c| c + p * w)
+    };
+    p * x
+}
+
+//unpolarized Fresnel reflectance of a dielectric, eta = n_transmitted / n_incident
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let cos_i = cos_i.abs().min(1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0; //total internal reflection
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let r_parl = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perp = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (r_parl * r_parl + r_perp * r_perp) / 2.0
+}
+
+pub fn schlick_weight(cosine: f64) -> f64 {
+    (1.0 - cosine).max(0.0).min(1.0).powi(5)
+}
+
+pub fn fresnel_schlick(f0: &Color, cosine: f64) -> Color {
+    *f0 + schlick_weight(cosine) * (Color::same(1.0) - *f0)
+}
+
+pub fn reflect_local(wo: &Vec3, h: &Vec3) -> Vec3 {
+    2.0 * dot(wo, h) * *h - *wo
+}
+
+//None on total internal reflection
+pub fn refract_local(wo: &Vec3, h: &Vec3, eta: f64) -> Option<Vec3> {
+    let cos_i = dot(wo, h);
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-*wo / eta + (cos_i / eta - cos_t) * *h)
+}
+
+//half vector of a refraction, oriented to +z
+pub fn refraction_half_vector(wo: &Vec3, wi: &Vec3, eta: f64) -> Vec3 {
+    let h = (eta * *wi + *wo).unit();
+    if h.z() < 0.0 {
+        -h
+    } else {
+        h
+    }
+}
+
+//reflectance of a conductor with complex index of refraction eta + ik, per channel
+pub fn fresnel_conductor(cos_i: f64, eta: &Color, k: &Color) -> Color {
+    let cos_i = cos_i.abs().min(1.0);
+    let channel = |eta: f64, k: f64| {
+        let cos2 = cos_i * cos_i;
+        let sin2 = 1.0 - cos2;
+        let (eta2, k2) = (eta * eta, k * k);
+        let t0 = eta2 - k2 - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+        let t2 = 2.0 * cos_i * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        0.5 * (rp + rs)
+    };
+    Color::new(
+        channel(eta.x(), k.x()),
+        channel(eta.y(), k.y()),
+        channel(eta.z(), k.z()),
+    )
+}
@@ -0,0 +1,126 @@
+use crate::hittable::HitRecord;
+use crate::material::microfacet::fresnel_dielectric;
+use crate::material::{shading_frame, BSDFSample, Lobe, Material};
+use crate::texture::{SolidColor, Texture};
+use crate::utility::random_double;
+use crate::utility::ray::Ray;
+use crate::utility::vec3::*;
+
+#[derive(Clone)]
+pub enum MixWeight<T: Texture> {
+    Mask(T),      //0 picks the first material, 1 the second
+    Fresnel(f64), //the second one is seen with the reflectance of a dielectric of this index
+}
+
+// Two materials on one surface, like rust on metal or dirt between grass.
+// Each hit samples one of them with the weight's probability. The BSDF and pdf are the blend of
+// both, so a direction drawn by either one is weighted as the mix would scatter it.
+#[derive(Clone)]
+pub struct MixMaterial<A: Material, B: Material, T: Texture> {
+    pub a: A,
+    pub b: B,
+    pub weight: MixWeight<T>,
+}
+
+impl<A: Material, B: Material, T: Texture> MixMaterial<A, B, T> {
+    pub fn new(a: A, b: B, mask: T) -> Self {
+        Self {
+            a,
+            b,
+            weight: MixWeight::Mask(mask),
+        }
+    }
+
+    //probability of the second material
+    fn weight(&self, r_in: &Ray, rec: &HitRecord) -> f64 {
+        match &self.weight {
+            MixWeight::Mask(mask) => mask.scalar_at(rec).max(0.0).min(1.0),
+            MixWeight::Fresnel(ior) => {
+                let (uvw, entering) = shading_frame(r_in, rec);
+                let cos = dot(&(-r_in.direction().unit()), &uvw.w());
+                fresnel_dielectric(cos, if entering { *ior } else { 1.0 / ior })
+            }
+        }
+    }
+}
+
+impl<A: Material, B: Material> MixMaterial<A, B, SolidColor> {
+    pub fn fresnel(a: A, b: B, ior: f64) -> Self {
+        Self {
+            a,
+            b,
+            weight: MixWeight::Fresnel(ior),
+        }
+    }
+}
+
+impl<A: Material, B: Material, T: Texture> Material for MixMaterial<A, B, T> {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BSDFSample> {
+        let sample = if random_double() < self.weight(r_in, rec) {
+            self.b.sample(r_in, rec)?
+        } else {
+            self.a.sample(r_in, rec)?
+        };
+        //a specular direction is only ever drawn by the material that has it
+        if sample.lobe.is_specular() {
+            return Some(sample);
+        }
+        let f = self.eval(r_in, rec, &sample.scattered);
+        let pdf = self.pdf(r_in, rec, &sample.scattered);
+        BSDFSample::new(sample.scattered, f, pdf, sample.lobe)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let w = self.weight(r_in, rec);
+        (1.0 - w) * self.a.eval(r_in, rec, scattered) + w * self.b.eval(r_in, rec, scattered)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let w = self.weight(r_in, rec);
+        (1.0 - w) * self.a.pdf(r_in, rec, scattered) + w * self.b.pdf(r_in, rec, scattered)
+    }
+
+    fn lobes(&self) -> Lobe {
+        self.a.lobes() | self.b.lobes()
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord, u: f64, v: f64, p: &Point3) -> Color {
+        let w = self.weight(r_in, rec);
+        (1.0 - w) * self.a.emitted(r_in, rec, u, v, p) + w * self.b.emitted(r_in, rec, u, v, p)
+    }
+}
+
+//a glowing surface that still reflects, the emission of both is added on top of the base BSDF
+#[derive(Clone, Default)]
+pub struct AddMaterial<M: Material, E: Material> {
+    pub base: M,
+    pub emission: E,
+}
+
+impl<M: Material, E: Material> AddMaterial<M, E> {
+    pub fn new(base: M, emission: E) -> Self {
+        Self { base, emission }
+    }
+}
+
+impl<M: Material, E: Material> Material for AddMaterial<M, E> {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BSDFSample> {
+        self.base.sample(r_in, rec)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.base.eval(r_in, rec, scattered)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.base.pdf(r_in, rec, scattered)
+    }
+
+    fn lobes(&self) -> Lobe {
+        self.base.lobes()
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord, u: f64, v: f64, p: &Point3) -> Color {
+        self.base.emitted(r_in, rec, u, v, p) + self.emission.emitted(r_in, rec, u, v, p)
+    }
+}
@@ -0,0 +1,463 @@
+pub mod anisotropic;
+pub mod conductor;
+pub mod diffuse;
+pub mod hair;
+pub mod layered;
+pub mod microfacet;
+pub mod mix;
+pub mod normal_map;
+pub mod phase;
+pub mod principled;
+pub mod rough_dielectric;
+pub mod subsurface;
+pub mod thin_film;
+
+use crate::hittable::HitRecord;
+use crate::material::thin_film::ThinFilm;
+use crate::pdf::onb::ONB;
+use crate::pdf::{CosPDF, PDF};
+use crate::texture::{SolidColor, Texture};
+use crate::utility::random_double;
+use crate::utility::ray::Ray;
+use crate::utility::vec3::*;
+use std::f64::consts::PI;
+use std::ops::BitOr;
+use std::sync::Arc;
+
+//kinds of scattering, as bit flags
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Lobe(u8);
+
+impl Lobe {
+    pub const NONE: Lobe = Lobe(0);
+    pub const DIFFUSE: Lobe = Lobe(1);
+    pub const GLOSSY: Lobe = Lobe(1 << 1);
+    pub const SPECULAR: Lobe = Lobe(1 << 2); //a single direction, light sampling never finds it
+    pub const TRANSMISSION: Lobe = Lobe(1 << 3); //through the surface, reflection otherwise
+
+    pub fn contains(self, other: Lobe) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn intersects(self, other: Lobe) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub fn is_specular(self) -> bool {
+        self.contains(Lobe::SPECULAR)
+    }
+
+    //whether eval and pdf can be nonzero, so that sampling the lights pays off
+    pub fn non_specular(self) -> bool {
+        self.intersects(Lobe::DIFFUSE | Lobe::GLOSSY)
+    }
+}
+
+impl BitOr for Lobe {
+    type Output = Self;
+    fn bitor(self, other: Self) -> Self {
+        Lobe(self.0 | other.0)
+    }
+}
+
+//a scattered ray drawn by a material
+#[derive(Clone)]
+pub struct BSDFSample {
+    pub scattered: Ray,
+    pub weight: Color, //BSDF times cosine over pdf, the light along the ray is scaled by it
+    pub pdf: f64,      //solid angle density, unused for specular lobes
+    pub lobe: Lobe,
+}
+
+impl BSDFSample {
+    //None when the direction cannot be drawn, f is the BSDF times cosine
+    pub fn new(scattered: Ray, f: Color, pdf: f64, lobe: Lobe) -> Option<Self> {
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(Self {
+            scattered,
+            weight: f / pdf,
+            pdf,
+            lobe,
+        })
+    }
+
+    pub fn specular(scattered: Ray, weight: Color, lobe: Lobe) -> Self {
+        Self {
+            scattered,
+            weight,
+            pdf: 0.0,
+            lobe: lobe | Lobe::SPECULAR,
+        }
+    }
+}
+
+// A material scatters light with sample, which draws a direction, and reports the BSDF and the
+// density of sample towards any direction with eval and pdf, which light sampling relies on.
+// Specular lobes are only ever found by sample.
+pub trait Material: Send + Sync {
+    //None when the light is absorbed
+    fn sample(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<BSDFSample> {
+        None
+    }
+
+    //BSDF times cosine towards the scattered ray
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> Color {
+        Color::default()
+    }
+
+    fn pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        0.0
+    }
+
+    //every lobe sample may draw from
+    fn lobes(&self) -> Lobe {
+        Lobe::NONE
+    }
+
+    fn emitted(&self, _r_in: &Ray, _rec: &HitRecord, _u: f64, _v: f64, _p: &Point3) -> Color {
+        Color::default()
+    }
+}
+
+// Shared materials of any kind, as the material library hands them out.
+impl Material for Arc<dyn Material> {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BSDFSample> {
+        (**self).sample(r_in, rec)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        (**self).eval(r_in, rec, scattered)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        (**self).pdf(r_in, rec, scattered)
+    }
+
+    fn lobes(&self) -> Lobe {
+        (**self).lobes()
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord, u: f64, v: f64, p: &Point3) -> Color {
+        (**self).emitted(r_in, rec, u, v, p)
+    }
+}
+
+//a BSDF set up at one hit point, drawing directions from its own pdf
+pub trait BSDF: PDF {
+    //the BSDF times the cosine of the scattered direction
+    fn eval(&self, direction: &Vec3) -> Color;
+
+    //lobe is the kind of the whole BSDF, transmission is told by the side of the direction
+    fn sample(&self, r_in: &Ray, rec: &HitRecord, lobe: Lobe) -> Option<BSDFSample> {
+        let direction = self.generate();
+        let lobe = if dot(&direction, &rec.normal) * dot(&r_in.direction(), &rec.normal) > 0.0 {
+            lobe | Lobe::TRANSMISSION
+        } else {
+            lobe
+        };
+        let scattered = Ray::new(&rec.p, &direction, r_in.time());
+        BSDFSample::new(
+            scattered,
+            self.eval(&direction),
+            self.value(&direction),
+            lobe,
+        )
+    }
+}
+
+//shading frame with w on the side of the incoming ray, and whether the ray enters the surface
+pub fn shading_frame(r_in: &Ray, rec: &HitRecord) -> (ONB, bool) {
+    let outward_normal = if rec.front_face {
+        rec.normal
+    } else {
+        -rec.normal
+    };
+    let entering = dot(&r_in.direction(), &outward_normal) < 0.0;
+    let n = if entering {
+        outward_normal
+    } else {
+        -outward_normal
+    };
+    (ONB::build_from_w(&n), entering)
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct EmptyMaterial {}
+impl Material for EmptyMaterial {}
+pub const DEFAULT_MATERIAL: EmptyMaterial = EmptyMaterial {};
+
+#[derive(Clone, Default)]
+pub struct Lambertian<T: Texture> {
+    pub albedo: T,
+}
+
+impl<T: Texture> Lambertian<T> {
+    pub fn new(a: T) -> Self {
+        Lambertian { albedo: a }
+    }
+}
+
+impl Lambertian<SolidColor> {
+    pub fn new_from_color(color: &Color) -> Self {
+        Lambertian {
+            albedo: SolidColor::new(color),
+        }
+    }
+}
+
+impl<T: Texture> Material for Lambertian<T> {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BSDFSample> {
+        let direction = CosPDF::new(&rec.normal).generate();
+        let scattered = Ray::new(&rec.p, &direction, r_in.time());
+        let pdf = self.pdf(r_in, rec, &scattered);
+        BSDFSample::new(
+            scattered,
+            self.eval(r_in, rec, &scattered),
+            pdf,
+            Lobe::DIFFUSE,
+        )
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.albedo.value_at(rec) * self.pdf(r_in, rec, scattered)
+    }
+
+    fn pdf(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let cosine = dot(&rec.normal, &scattered.direction().unit());
+        if cosine < 0.0 {
+            0.0
+        } else {
+            cosine / PI
+        }
+    }
+
+    fn lobes(&self) -> Lobe {
+        Lobe::DIFFUSE
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Metal {
+    pub albedo: Color,
+    pub fuzz: f64,
+}
+
+impl Metal {
+    pub fn new(a: &Color, f: f64) -> Self {
+        Metal {
+            albedo: *a,
+            fuzz: f.min(1.0),
+        }
+    }
+}
+
+impl Material for Metal {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BSDFSample> {
+        let reflected = reflect(&r_in.direction().unit(), &rec.normal)
+            + self.fuzz * Vec3::random_in_unit_sphere();
+        let scattered = Ray::new(&rec.p, &reflected, r_in.time());
+        if self.fuzz <= 0.0 {
+            return Some(BSDFSample::specular(scattered, self.albedo, Lobe::NONE));
+        }
+        if dot(&reflected, &rec.normal) <= 0.0 {
+            return None;
+        }
+        let pdf = self.pdf(r_in, rec, &scattered);
+        BSDFSample::new(scattered, self.albedo * pdf, pdf, Lobe::GLOSSY)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.albedo * self.pdf(r_in, rec, scattered)
+    }
+
+    // The fuzzed direction points at a uniform point of the ball of radius fuzz around the
+    // mirror direction. Its density is the part of the ball's volume seen along the direction,
+    // integrated over r^2 dr between where the direction enters and leaves the ball.
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let direction = scattered.direction().unit();
+        if self.fuzz <= 0.0 || dot(&direction, &rec.normal) <= 0.0 {
+            return 0.0;
+        }
+        let mirror = reflect(&r_in.direction().unit(), &rec.normal);
+        let cos = dot(&direction, &mirror);
+        let fuzz2 = self.fuzz * self.fuzz;
+        let disc = cos * cos - 1.0 + fuzz2;
+        if disc < 0.0 {
+            return 0.0;
+        }
+        let far = cos + disc.sqrt();
+        let near = (cos - disc.sqrt()).max(0.0);
+        if far <= 0.0 {
+            return 0.0;
+        }
+        (far.powi(3) - near.powi(3)) / (4.0 * PI * fuzz2 * self.fuzz)
+    }
+
+    fn lobes(&self) -> Lobe {
+        if self.fuzz <= 0.0 {
+            Lobe::SPECULAR
+        } else {
+            Lobe::GLOSSY
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Dielectric {
+    pub ir: f64, //index of refraction
+    pub film: Option<ThinFilm>,
+}
+
+impl Dielectric {
+    pub fn new(index_of_refraction: f64) -> Self {
+        Dielectric {
+            ir: index_of_refraction,
+            film: None,
+        }
+    }
+
+    //a soap bubble is a film on glass with an index of refraction of 1
+    pub fn with_film(mut self, film: ThinFilm) -> Self {
+        self.film = Some(film);
+        self
+    }
+}
+
+impl Material for Dielectric {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BSDFSample> {
+        let refraction_ratio = if rec.front_face {
+            1.0 / self.ir
+        } else {
+            self.ir
+        };
+        let unit_dir = r_in.direction().unit();
+        let cos_theta = dot(&(-unit_dir), &rec.normal).min(1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let cannot_refract = refraction_ratio * sin_theta > 1.0;
+
+        if let Some(film) = &self.film {
+            //the film coats the outside, the reflectance is coloured so choose by its average
+            let (n_incident, n_other) = if rec.front_face {
+                (1.0, self.ir)
+            } else {
+                (self.ir, 1.0)
+            };
+            let r = film.at(rec).reflectance(
+                cos_theta,
+                n_incident,
+                &Color::same(n_other),
+                &Color::default(),
+            );
+            let p = (r.x() + r.y() + r.z()) / 3.0;
+            let (direction, weight, lobe) = if cannot_refract || random_double() < p {
+                (reflect(&unit_dir, &rec.normal), r / p.max(1e-6), Lobe::NONE)
+            } else {
+                (
+                    refract(&unit_dir, &rec.normal, refraction_ratio),
+                    (Color::same(1.0) - r) / (1.0 - p),
+                    Lobe::TRANSMISSION,
+                )
+            };
+            let scattered = Ray::new(&rec.p, &direction, r_in.time());
+            return Some(BSDFSample::specular(scattered, weight, lobe));
+        }
+
+        let (direction, lobe) =
+            if cannot_refract || reflectance(cos_theta, refraction_ratio) > random_double() {
+                (reflect(&unit_dir, &rec.normal), Lobe::NONE)
+            } else {
+                (
+                    refract(&unit_dir, &rec.normal, refraction_ratio),
+                    Lobe::TRANSMISSION,
+                )
+            };
+
+        let scattered = Ray::new(&rec.p, &direction, r_in.time());
+        Some(BSDFSample::specular(scattered, Color::same(1.0), lobe))
+    }
+
+    fn lobes(&self) -> Lobe {
+        Lobe::SPECULAR | Lobe::TRANSMISSION
+    }
+}
+
+fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
+    // Use Schlick's approximation for reflectance.
+    let r0 = ((1.0 - ref_idx) / (1.0 + ref_idx)).powi(2);
+    r0 + (1.0 - r0) * ((1.0 - cosine).powi(5))
+}
+
+#[derive(Clone, Default)]
+pub struct DiffuseLight<T: Texture> {
+    emit: T,
+}
+
+impl<T: Texture> DiffuseLight<T> {
+    pub fn new(a: T) -> Self {
+        Self { emit: a }
+    }
+}
+
+impl DiffuseLight<SolidColor> {
+    pub fn new_from_color(color: &Color) -> Self {
+        Self {
+            emit: SolidColor::new(color),
+        }
+    }
+}
+
+impl<T: Texture> Material for DiffuseLight<T> {
+    fn emitted(&self, _r_in: &Ray, rec: &HitRecord, u: f64, v: f64, p: &Point3) -> Color {
+        if rec.front_face {
+            self.emit.filtered(u, v, p, &rec.footprint)
+        } else {
+            Color::default()
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Isotropic<T: Texture> {
+    pub albedo: T,
+}
+
+impl<T: Texture> Isotropic<T> {
+    pub fn new(a: T) -> Self {
+        Self { albedo: a }
+    }
+}
+
+impl Isotropic<SolidColor> {
+    pub fn new_from_color(color: &Color) -> Self {
+        Self {
+            albedo: SolidColor::new(color),
+        }
+    }
+}
+
+impl<T: Texture> Material for Isotropic<T> {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BSDFSample> {
+        let scattered = Ray::new(&rec.p, &Vec3::random_unit_vector(), r_in.time());
+        let albedo = self.albedo.value_at(rec);
+        BSDFSample::new(
+            scattered,
+            albedo / (4.0 * PI),
+            1.0 / (4.0 * PI),
+            Lobe::DIFFUSE,
+        )
+    }
+
+    fn eval(&self, _r_in: &Ray, rec: &HitRecord, _scattered: &Ray) -> Color {
+        self.albedo.value_at(rec) / (4.0 * PI)
+    }
+
+    fn pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn lobes(&self) -> Lobe {
+        Lobe::DIFFUSE
+    }
+}
@@ -0,0 +1,118 @@
+use crate::hittable::HitRecord;
+use crate::material::{BSDFSample, Lobe, Material};
+use crate::texture::Texture;
+use crate::utility::ray::Ray;
+use crate::utility::vec3::*;
+
+const BUMP_DELTA: f64 = 1e-3; //uv step for the height differences
+
+// Both wrappers shade the base material with a perturbed normal. The new normal is only used
+// when it stays on the side of the surface the ray comes from, otherwise the light would leak
+// through, so grazing hits fall back to the smooth normal.
+fn perturbed<'a>(r_in: &Ray, rec: &HitRecord<'a>, normal: &Vec3) -> HitRecord<'a> {
+    let mut shaded = rec.clone();
+    let wo = -r_in.direction();
+    let normal = normal.unit();
+    if dot(&normal, &rec.normal) > 0.0 && dot(&normal, &wo) * dot(&rec.geometric_normal, &wo) > 0.0
+    {
+        shaded.normal = normal;
+    }
+    shaded
+}
+
+//tangent space normal map, as baked by most modelling tools (OpenGL convention, green is +v)
+#[derive(Clone)]
+pub struct NormalMap<M: Material, T: Texture> {
+    pub base: M,
+    pub map: T,
+    pub strength: f64, //scales the tilt, 1 is the map as baked
+}
+
+impl<M: Material, T: Texture> NormalMap<M, T> {
+    pub fn new(base: M, map: T) -> Self {
+        Self {
+            base,
+            map,
+            strength: 1.0,
+        }
+    }
+
+    fn shade<'a>(&self, r_in: &Ray, rec: &HitRecord<'a>) -> HitRecord<'a> {
+        let c = 2.0 * self.map.value_at(rec) - Color::same(1.0);
+        let (t, b) = rec.shading_tangents();
+        let normal = self.strength * (c.x() * t + c.y() * b) + c.z().max(0.0) * rec.normal;
+        perturbed(r_in, rec, &normal)
+    }
+}
+
+impl<M: Material, T: Texture> Material for NormalMap<M, T> {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BSDFSample> {
+        self.base.sample(r_in, &self.shade(r_in, rec))
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.base.eval(r_in, &self.shade(r_in, rec), scattered)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.base.pdf(r_in, &self.shade(r_in, rec), scattered)
+    }
+
+    fn lobes(&self) -> Lobe {
+        self.base.lobes()
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord, u: f64, v: f64, p: &Point3) -> Color {
+        self.base.emitted(r_in, rec, u, v, p)
+    }
+}
+
+//height map, the normal tilts against the slope of the height along u and v
+#[derive(Clone)]
+pub struct BumpMap<M: Material, T: Texture> {
+    pub base: M,
+    pub height: T,
+    pub scale: f64, //height change per unit of uv
+}
+
+impl<M: Material, T: Texture> BumpMap<M, T> {
+    pub fn new(base: M, height: T, scale: f64) -> Self {
+        Self {
+            base,
+            height,
+            scale,
+        }
+    }
+
+    fn shade<'a>(&self, r_in: &Ray, rec: &HitRecord<'a>) -> HitRecord<'a> {
+        let (u, v, p) = (rec.u, rec.v, &rec.p);
+        let h = self.height.scalar(u, v, p);
+        let dh_du = (self.height.scalar(u + BUMP_DELTA, v, p) - h) / BUMP_DELTA;
+        let dh_dv = (self.height.scalar(u, v + BUMP_DELTA, p) - h) / BUMP_DELTA;
+        let (t, b) = rec.shading_tangents();
+        let normal = rec.normal - self.scale * (dh_du * t + dh_dv * b);
+        perturbed(r_in, rec, &normal)
+    }
+}
+
+impl<M: Material, T: Texture> Material for BumpMap<M, T> {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BSDFSample> {
+        self.base.sample(r_in, &self.shade(r_in, rec))
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.base.eval(r_in, &self.shade(r_in, rec), scattered)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.base.pdf(r_in, &self.shade(r_in, rec), scattered)
+    }
+
+    fn lobes(&self) -> Lobe {
+        self.base.lobes()
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord, u: f64, v: f64, p: &Point3) -> Color {
+        self.base.emitted(r_in, rec, u, v, p)
+    }
+}
@@ -0,0 +1,185 @@
+use crate::hittable::HitRecord;
+use crate::material::{BSDFSample, Lobe, Material};
+use crate::pdf::onb::ONB;
+use crate::texture::{SolidColor, Texture};
+use crate::utility::random_double;
+use crate::utility::ray::Ray;
+use crate::utility::vec3::*;
+use std::f64::consts::PI;
+
+// How a particle of a medium redirects light, as a density over the sphere of directions.
+// All of these depend only on the angle between the direction the light travels in and the
+// direction it leaves along, so they are given as a function of the cosine of that angle.
+pub trait PhaseFunction: Send + Sync {
+    //density towards a direction at cos_theta from the direction of travel, integrates to 1
+    fn value(&self, cos_theta: f64) -> f64;
+
+    //cos_theta of a direction drawn proportionally to value
+    fn sample_cos(&self) -> f64;
+
+    fn lobe(&self) -> Lobe {
+        Lobe::DIFFUSE
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct IsotropicPhase;
+
+impl PhaseFunction for IsotropicPhase {
+    fn value(&self, _cos_theta: f64) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn sample_cos(&self) -> f64 {
+        1.0 - 2.0 * random_double()
+    }
+}
+
+// Henyey-Greenstein, g in (-1, 1) is the mean cosine: positive scatters forward like fog and
+// clouds, negative backward, 0 is isotropic.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct HenyeyGreenstein {
+    pub g: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(g: f64) -> Self {
+        Self {
+            g: g.max(-0.999).min(0.999),
+        }
+    }
+}
+
+impl PhaseFunction for HenyeyGreenstein {
+    fn value(&self, cos_theta: f64) -> f64 {
+        let g = self.g;
+        let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+        (1.0 - g * g) / (4.0 * PI * denom * denom.sqrt())
+    }
+
+    fn sample_cos(&self) -> f64 {
+        let g = self.g;
+        if g.abs() < 1e-3 {
+            return 1.0 - 2.0 * random_double();
+        }
+        let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * random_double());
+        ((1.0 + g * g - s * s) / (2.0 * g)).max(-1.0).min(1.0)
+    }
+
+    fn lobe(&self) -> Lobe {
+        if self.g.abs() < 1e-3 {
+            Lobe::DIFFUSE
+        } else {
+            Lobe::GLOSSY
+        }
+    }
+}
+
+// Blend of two Henyey-Greenstein lobes, usually a strong forward one and a weak backward one,
+// which fits clouds and smoke better than a single lobe.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct DoubleHenyeyGreenstein {
+    pub first: HenyeyGreenstein,
+    pub second: HenyeyGreenstein,
+    pub weight: f64, //of the first lobe
+}
+
+impl DoubleHenyeyGreenstein {
+    pub fn new(g1: f64, g2: f64, weight: f64) -> Self {
+        Self {
+            first: HenyeyGreenstein::new(g1),
+            second: HenyeyGreenstein::new(g2),
+            weight: weight.max(0.0).min(1.0),
+        }
+    }
+}
+
+impl PhaseFunction for DoubleHenyeyGreenstein {
+    fn value(&self, cos_theta: f64) -> f64 {
+        self.weight * self.first.value(cos_theta)
+            + (1.0 - self.weight) * self.second.value(cos_theta)
+    }
+
+    fn sample_cos(&self) -> f64 {
+        if random_double() < self.weight {
+            self.first.sample_cos()
+        } else {
+            self.second.sample_cos()
+        }
+    }
+
+    fn lobe(&self) -> Lobe {
+        self.first.lobe() | self.second.lobe()
+    }
+}
+
+// Scattering by particles much smaller than the wavelength, like the molecules of the air.
+// Forward and backward equally, least to the side.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Rayleigh;
+
+impl PhaseFunction for Rayleigh {
+    fn value(&self, cos_theta: f64) -> f64 {
+        3.0 / (16.0 * PI) * (1.0 + cos_theta * cos_theta)
+    }
+
+    fn sample_cos(&self) -> f64 {
+        //the cdf is (3 mu + mu^3) / 8 + 1 / 2, inverted with Cardano's formula
+        let z = 4.0 * random_double() - 2.0;
+        let root = (z * z + 1.0).sqrt();
+        ((z + root).cbrt() + (z - root).cbrt()).max(-1.0).min(1.0)
+    }
+}
+
+// Scattering inside a medium, such as ConstantMedium, with a phase function and the albedo of
+// its particles. The albedo is the share of light scattered rather than absorbed.
+#[derive(Clone, Default)]
+pub struct PhaseMaterial<T: Texture, P: PhaseFunction> {
+    pub albedo: T,
+    pub phase: P,
+}
+
+impl<T: Texture, P: PhaseFunction> PhaseMaterial<T, P> {
+    pub fn new(a: T, phase: P) -> Self {
+        Self { albedo: a, phase }
+    }
+
+    fn cos_theta(r_in: &Ray, scattered: &Ray) -> f64 {
+        dot(&r_in.direction().unit(), &scattered.direction().unit())
+    }
+}
+
+impl<P: PhaseFunction> PhaseMaterial<SolidColor, P> {
+    pub fn new_from_color(color: &Color, phase: P) -> Self {
+        Self {
+            albedo: SolidColor::new(color),
+            phase,
+        }
+    }
+}
+
+impl<T: Texture, P: PhaseFunction> Material for PhaseMaterial<T, P> {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BSDFSample> {
+        let cos_theta = self.phase.sample_cos();
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_double();
+        let frame = ONB::build_from_w(&r_in.direction());
+        let direction = frame.local(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        let scattered = Ray::new(&rec.p, &direction, r_in.time());
+        let value = self.phase.value(cos_theta);
+        let albedo = self.albedo.value_at(rec);
+        BSDFSample::new(scattered, albedo * value, value, self.phase.lobe())
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.albedo.value_at(rec) * self.phase.value(Self::cos_theta(r_in, scattered))
+    }
+
+    fn pdf(&self, r_in: &Ray, _rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.phase.value(Self::cos_theta(r_in, scattered))
+    }
+
+    fn lobes(&self) -> Lobe {
+        self.phase.lobe()
+    }
+}
@@ -0,0 +1,265 @@
+use crate::hittable::HitRecord;
+use crate::material::microfacet::*;
+use crate::material::{shading_frame, BSDFSample, Lobe, Material, BSDF};
+use crate::pdf::onb::ONB;
+use crate::pdf::PDF;
+use crate::texture::{SolidColor, Texture};
+use crate::utility::color::luminance;
+use crate::utility::random_double;
+use crate::utility::ray::Ray;
+use crate::utility::vec3::*;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+//Disney principled BSDF (Burley 2012, 2015), every parameter is a texture in [0, 1] except ior
+#[derive(Clone)]
+pub struct Principled {
+    pub base_color: Arc<dyn Texture>,
+    pub metallic: Arc<dyn Texture>,
+    pub roughness: Arc<dyn Texture>,
+    pub specular: Arc<dyn Texture>,
+    pub specular_tint: Arc<dyn Texture>,
+    pub sheen: Arc<dyn Texture>,
+    pub clearcoat: Arc<dyn Texture>,
+    pub clearcoat_gloss: Arc<dyn Texture>,
+    pub transmission: Arc<dyn Texture>,
+    pub ior: Arc<dyn Texture>,
+}
+
+fn value(x: f64) -> Arc<dyn Texture> {
+    Arc::new(SolidColor::new_from_value(x))
+}
+
+impl Principled {
+    pub fn new(base_color: Arc<dyn Texture>) -> Self {
+        Self {
+            base_color,
+            metallic: value(0.0),
+            roughness: value(0.5),
+            specular: value(0.5),
+            specular_tint: value(0.0),
+            sheen: value(0.0),
+            clearcoat: value(0.0),
+            clearcoat_gloss: value(1.0),
+            transmission: value(0.0),
+            ior: value(1.5),
+        }
+    }
+
+    pub fn new_from_color(color: &Color) -> Self {
+        Self::new(Arc::new(SolidColor::new(color)))
+    }
+
+    fn bsdf(&self, r_in: &Ray, rec: &HitRecord) -> PrincipledBSDF {
+        PrincipledBSDF::new(self, r_in, rec)
+    }
+}
+
+impl Material for Principled {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BSDFSample> {
+        let bsdf = self.bsdf(r_in, rec);
+        let lobe = if bsdf.lobe_weights[0] > 0.0 {
+            Lobe::DIFFUSE | Lobe::GLOSSY
+        } else {
+            Lobe::GLOSSY
+        };
+        bsdf.sample(r_in, rec, lobe)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.bsdf(r_in, rec).eval(&scattered.direction())
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.bsdf(r_in, rec).value(&scattered.direction())
+    }
+
+    fn lobes(&self) -> Lobe {
+        Lobe::DIFFUSE | Lobe::GLOSSY | Lobe::TRANSMISSION
+    }
+}
+
+//the textures evaluated at one hit point, in the shading frame facing the incoming ray
+#[derive(Copy, Clone)]
+pub struct PrincipledBSDF {
+    uvw: ONB,
+    wo: Vec3,
+    eta: f64, //inside over outside when entering
+    base_color: Color,
+    metallic: f64,
+    sheen: f64,
+    clearcoat: f64,
+    transmission: f64,
+    specular_f0: Color,
+    roughness: f64,
+    ggx: Microfacet,
+    clearcoat_alpha: f64,
+    lobe_weights: [f64; 4], //diffuse, specular, clearcoat, transmission
+}
+
+impl PrincipledBSDF {
+    pub fn new(mat: &Principled, r_in: &Ray, rec: &HitRecord) -> Self {
+        let (uvw, entering) = shading_frame(r_in, rec);
+        let wo = uvw.to_local(&(-r_in.direction().unit()));
+
+        let base_color = mat.base_color.value_at(rec);
+        let metallic = mat.metallic.scalar_at(rec).max(0.0).min(1.0);
+        let roughness = mat.roughness.scalar_at(rec).max(0.0).min(1.0);
+        let specular = mat.specular.scalar_at(rec).max(0.0);
+        let specular_tint = mat.specular_tint.scalar_at(rec);
+        let sheen = mat.sheen.scalar_at(rec).max(0.0);
+        let clearcoat = mat.clearcoat.scalar_at(rec).max(0.0);
+        let clearcoat_gloss = mat.clearcoat_gloss.scalar_at(rec);
+        let transmission = mat.transmission.scalar_at(rec).max(0.0).min(1.0);
+        let ior = mat.ior.scalar_at(rec).max(1.0 + 1e-4);
+
+        let lum = luminance(&base_color);
+        let tint = if lum > 0.0 {
+            base_color / lum
+        } else {
+            Color::same(1.0)
+        };
+        let dielectric_f0 =
+            0.08 * specular * (Color::same(1.0 - specular_tint) + specular_tint * tint);
+        let specular_f0 = (1.0 - metallic) * dielectric_f0 + metallic * base_color;
+
+        let diffuse_weight = (1.0 - metallic) * (1.0 - transmission);
+        let specular_weight = 1.0 - (1.0 - metallic) * transmission;
+        let transmission_weight = (1.0 - metallic) * transmission;
+        let f = fresnel_schlick(&specular_f0, wo.z().abs());
+        let mut lobe_weights = [
+            diffuse_weight,
+            specular_weight * (0.25 + 0.75 * luminance(&f).min(1.0)),
+            0.25 * clearcoat,
+            transmission_weight,
+        ];
+        let sum: f64 = lobe_weights.iter().sum();
+        for weight in lobe_weights.iter_mut() {
+            *weight /= sum;
+        }
+
+        Self {
+            uvw,
+            wo,
+            eta: if entering { ior } else { 1.0 / ior },
+            base_color,
+            metallic,
+            sheen,
+            clearcoat,
+            transmission,
+            specular_f0,
+            roughness,
+            ggx: Microfacet::from_roughness(Distribution::GGX, roughness),
+            clearcoat_alpha: 0.1 + (0.001 - 0.1) * clearcoat_gloss,
+            lobe_weights,
+        }
+    }
+
+    fn clearcoat_d(&self, cos_h: f64) -> f64 {
+        //GTR1, Berry's distribution
+        let a2 = self.clearcoat_alpha * self.clearcoat_alpha;
+        (a2 - 1.0) / (PI * a2.ln() * (1.0 + (a2 - 1.0) * cos_h * cos_h))
+    }
+
+    fn sample_clearcoat(&self) -> Vec3 {
+        let a2 = self.clearcoat_alpha * self.clearcoat_alpha;
+        let cos_h = ((1.0 - a2.powf(1.0 - random_double())) / (1.0 - a2)).sqrt();
+        let sin_h = (1.0 - cos_h * cos_h).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_double();
+        Vec3::new(sin_h * phi.cos(), sin_h * phi.sin(), cos_h)
+    }
+}
+
+impl PDF for PrincipledBSDF {
+    fn value(&self, direction: &Vec3) -> f64 {
+        let wo = self.wo;
+        let wi = self.uvw.to_local(&direction.unit());
+        let [diffuse, specular, clearcoat, transmission] = self.lobe_weights;
+        if wo.z() <= 0.0 || wi.z() == 0.0 {
+            return 0.0;
+        }
+
+        let mut pdf = 0.0;
+        if wi.z() > 0.0 {
+            let h = (wo + wi).unit();
+            let cos_oh = dot(&wo, &h);
+            if cos_oh <= 0.0 {
+                return 0.0;
+            }
+            pdf += diffuse * wi.z() / PI
+                + specular * self.ggx.visible_d(&wo, &h) / (4.0 * cos_oh)
+                + clearcoat * self.clearcoat_d(h.z()) * h.z() / (4.0 * cos_oh);
+        }
+        if transmission > 0.0 {
+            pdf += transmission * self.ggx.dielectric_pdf(&wo, &wi, self.eta);
+        }
+        pdf
+    }
+
+    fn generate(&self) -> Vec3 {
+        let wo = self.wo;
+        let [diffuse, specular, clearcoat, _] = self.lobe_weights;
+        let x = random_double();
+        let wi = if x < diffuse {
+            Vec3::random_cosine_direction()
+        } else if x < diffuse + specular {
+            reflect_local(&wo, &self.ggx.sample_visible(&wo))
+        } else if x < diffuse + specular + clearcoat {
+            reflect_local(&wo, &self.sample_clearcoat())
+        } else {
+            self.ggx.sample_dielectric(&wo, self.eta)
+        };
+        self.uvw.local_vec(&wi)
+    }
+}
+
+impl BSDF for PrincipledBSDF {
+    fn eval(&self, direction: &Vec3) -> Color {
+        let wo = self.wo;
+        let wi = self.uvw.to_local(&direction.unit());
+        if wo.z() <= 0.0 || wi.z() == 0.0 {
+            return Color::default();
+        }
+
+        let mut f = Color::default();
+        if wi.z() > 0.0 {
+            let h = (wo + wi).unit();
+            let cos_d = dot(&wi, &h);
+            let cos_o_i = 4.0 * wo.z() * wi.z();
+
+            let diffuse_weight = (1.0 - self.metallic) * (1.0 - self.transmission);
+            if diffuse_weight > 0.0 {
+                let (fl, fv) = (schlick_weight(wi.z()), schlick_weight(wo.z()));
+                let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+                let fd = (1.0 + (fd90 - 1.0) * fl) * (1.0 + (fd90 - 1.0) * fv);
+                let sheen = self.sheen * schlick_weight(cos_d);
+                f += diffuse_weight * (fd / PI * self.base_color + Color::same(sheen));
+            }
+
+            let dg = self.ggx.d(&h) * self.ggx.g(&wo, &wi) / cos_o_i;
+            let specular_weight = 1.0 - (1.0 - self.metallic) * self.transmission;
+            f += specular_weight * dg * fresnel_schlick(&self.specular_f0, cos_d);
+
+            if self.clearcoat > 0.0 {
+                let g = Microfacet::ggx(0.25).g(&wo, &wi);
+                let fc = 0.04 + 0.96 * schlick_weight(cos_d);
+                f +=
+                    Color::same(0.25 * self.clearcoat * self.clearcoat_d(h.z()) * fc * g / cos_o_i);
+            }
+
+            f *= wi.z();
+        }
+
+        //glass reflects untinted and transmits the base colour
+        let transmission_weight = (1.0 - self.metallic) * self.transmission;
+        if transmission_weight > 0.0 {
+            let fd = transmission_weight * self.ggx.dielectric_eval(&wo, &wi, self.eta);
+            f += if wi.z() > 0.0 {
+                Color::same(fd)
+            } else {
+                fd * self.base_color
+            };
+        }
+        f
+    }
+}
@@ -0,0 +1,123 @@
+use crate::hittable::HitRecord;
+use crate::material::microfacet::*;
+use crate::material::{shading_frame, BSDFSample, Lobe, Material, BSDF};
+use crate::pdf::onb::ONB;
+use crate::pdf::PDF;
+use crate::texture::{SolidColor, Texture};
+use crate::utility::random_double;
+use crate::utility::ray::Ray;
+use crate::utility::vec3::*;
+
+//frosted glass, the roughness texture is sampled at the hit point
+#[derive(Clone)]
+pub struct RoughDielectric<T: Texture> {
+    pub ir: f64, //index of refraction
+    pub roughness: T,
+    pub distribution: Distribution,
+}
+
+impl<T: Texture> RoughDielectric<T> {
+    pub fn new(index_of_refraction: f64, roughness: T) -> Self {
+        Self {
+            ir: index_of_refraction,
+            roughness,
+            distribution: Distribution::GGX,
+        }
+    }
+
+    fn bsdf(&self, r_in: &Ray, rec: &HitRecord) -> RoughDielectricBSDF {
+        let (uvw, entering) = shading_frame(r_in, rec);
+        let roughness = self.roughness.scalar_at(rec);
+        RoughDielectricBSDF {
+            uvw,
+            wo: uvw.to_local(&(-r_in.direction().unit())),
+            microfacet: Microfacet::from_roughness(self.distribution, roughness),
+            eta: if entering { self.ir } else { 1.0 / self.ir },
+        }
+    }
+}
+
+impl RoughDielectric<SolidColor> {
+    pub fn new_from_value(index_of_refraction: f64, roughness: f64) -> Self {
+        Self::new(index_of_refraction, SolidColor::new_from_value(roughness))
+    }
+}
+
+impl<T: Texture> Material for RoughDielectric<T> {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BSDFSample> {
+        let bsdf = self.bsdf(r_in, rec);
+        if !bsdf.smooth() {
+            return bsdf.sample(r_in, rec, Lobe::GLOSSY);
+        }
+
+        //smooth glass, no light sampling through a delta lobe
+        let wo = bsdf.wo;
+        let n = Vec3::new(0.0, 0.0, 1.0);
+        let wi = if random_double() < fresnel_dielectric(wo.z(), bsdf.eta) {
+            reflect_local(&wo, &n)
+        } else {
+            refract_local(&wo, &n, bsdf.eta).unwrap_or_else(|| reflect_local(&wo, &n))
+        };
+        //radiance is compressed into the denser medium
+        let (weight, lobe) = if wi.z() < 0.0 {
+            (Color::same(1.0 / (bsdf.eta * bsdf.eta)), Lobe::TRANSMISSION)
+        } else {
+            (Color::same(1.0), Lobe::NONE)
+        };
+        let scattered = Ray::new(&rec.p, &bsdf.uvw.local_vec(&wi), r_in.time());
+        Some(BSDFSample::specular(scattered, weight, lobe))
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let bsdf = self.bsdf(r_in, rec);
+        if bsdf.smooth() {
+            return Color::default();
+        }
+        bsdf.eval(&scattered.direction())
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let bsdf = self.bsdf(r_in, rec);
+        if bsdf.smooth() {
+            return 0.0;
+        }
+        bsdf.value(&scattered.direction())
+    }
+
+    fn lobes(&self) -> Lobe {
+        Lobe::GLOSSY | Lobe::SPECULAR | Lobe::TRANSMISSION
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct RoughDielectricBSDF {
+    uvw: ONB,
+    wo: Vec3,
+    microfacet: Microfacet,
+    eta: f64,
+}
+
+impl RoughDielectricBSDF {
+    fn smooth(&self) -> bool {
+        self.microfacet.max_alpha() <= 1e-3
+    }
+}
+
+impl PDF for RoughDielectricBSDF {
+    fn value(&self, direction: &Vec3) -> f64 {
+        let wi = self.uvw.to_local(&direction.unit());
+        self.microfacet.dielectric_pdf(&self.wo, &wi, self.eta)
+    }
+
+    fn generate(&self) -> Vec3 {
+        self.uvw
+            .local_vec(&self.microfacet.sample_dielectric(&self.wo, self.eta))
+    }
+}
+
+impl BSDF for RoughDielectricBSDF {
+    fn eval(&self, direction: &Vec3) -> Color {
+        let wi = self.uvw.to_local(&direction.unit());
+        Color::same(self.microfacet.dielectric_eval(&self.wo, &wi, self.eta))
+    }
+}
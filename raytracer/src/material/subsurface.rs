@@ -0,0 +1,115 @@
+use crate::hittable::HitRecord;
+use crate::material::microfacet::{fresnel_dielectric, reflect_local, refract_local};
+use crate::material::{shading_frame, BSDFSample, Lobe, Material};
+use crate::utility::random_double;
+use crate::utility::ray::Ray;
+use crate::utility::vec3::*;
+use std::f64::INFINITY;
+
+// Volumetric random walk inside a closed surface, for skin, marble or wax.
+// A ray that refracted in travels to the next boundary hit. A free-flight distance is drawn on
+// that segment, as in ConstantMedium::hit: if it is shorter, the ray scatters inside the
+// volume, otherwise it meets the boundary and refracts out or reflects back in.
+// Every step is a bounce of ray_color, so dense media need a generous bounce depth. The steps
+// are all drawn as specular samples, since light sampling from the boundary cannot reach them.
+#[derive(Debug, Copy, Clone)]
+pub struct Subsurface {
+    pub sigma_s: Color, //scattering coefficient per unit length
+    pub sigma_a: Color, //absorption coefficient per unit length
+    pub ior: f64,
+}
+
+impl Subsurface {
+    pub fn new(sigma_s: &Color, sigma_a: &Color, ior: f64) -> Self {
+        Self {
+            sigma_s: *sigma_s,
+            sigma_a: *sigma_a,
+            ior,
+        }
+    }
+
+    //albedo is the colour of the lit material, the mean free path how far light travels inside
+    pub fn from_albedo(albedo: &Color, mean_free_path: &Color, ior: f64) -> Self {
+        let mut sigma_s = Color::default();
+        let mut sigma_a = Color::default();
+        for i in 0..3 {
+            //single scattering albedo giving this multiple scattering albedo (van de Hulst)
+            let a = albedo[i].max(0.0).min(1.0);
+            let s = 4.09712 + 4.20863 * a - (9.59217 + 41.6808 * a + 17.7126 * a * a).sqrt();
+            let single = 1.0 - s * s;
+            let sigma_t = 1.0 / mean_free_path[i].max(1e-6);
+            sigma_s[i] = single * sigma_t;
+            sigma_a[i] = (1.0 - single) * sigma_t;
+        }
+        Self::new(&sigma_s, &sigma_a, ior)
+    }
+
+    fn sigma_t(&self) -> Color {
+        self.sigma_s + self.sigma_a
+    }
+
+    //smooth interface, eta is the index on the far side over the one on the ray's side
+    fn interface(&self, r_in: &Ray, rec: &HitRecord, eta: f64, weight: Color) -> BSDFSample {
+        let (uvw, _) = shading_frame(r_in, rec);
+        let wo = uvw.to_local(&(-r_in.direction().unit()));
+        let n = Vec3::new(0.0, 0.0, 1.0);
+        let wi = if random_double() < fresnel_dielectric(wo.z(), eta) {
+            reflect_local(&wo, &n)
+        } else {
+            refract_local(&wo, &n, eta).unwrap_or_else(|| reflect_local(&wo, &n))
+        };
+        let lobe = if wi.z() < 0.0 {
+            Lobe::TRANSMISSION
+        } else {
+            Lobe::NONE
+        };
+        let scattered = Ray::new(&rec.p, &uvw.local_vec(&wi), r_in.time());
+        BSDFSample::specular(scattered, weight, lobe)
+    }
+}
+
+impl Material for Subsurface {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BSDFSample> {
+        let (_, entering) = shading_frame(r_in, rec);
+        if entering {
+            return Some(self.interface(r_in, rec, self.ior, Color::same(1.0)));
+        }
+
+        // The ray came from inside: sample a free flight from its origin, picking the channel
+        // to sample uniformly so that colourful media stay well behaved.
+        let sigma_t = self.sigma_t();
+        let ray_length = r_in.direction().length();
+        let distance_to_boundary = rec.t * ray_length;
+        let channel = ((random_double() * 3.0) as usize).min(2);
+        let distance = if sigma_t[channel] > 0.0 {
+            -(1.0 - random_double()).ln() / sigma_t[channel]
+        } else {
+            INFINITY
+        };
+        let transmittance = |d: f64| {
+            Color::new(
+                (-sigma_t.x() * d).exp(),
+                (-sigma_t.y() * d).exp(),
+                (-sigma_t.z() * d).exp(),
+            )
+        };
+
+        if distance < distance_to_boundary {
+            //scattering event in the volume, isotropic phase function
+            let tr = transmittance(distance);
+            let pdf = (sigma_t * tr).x() + (sigma_t * tr).y() + (sigma_t * tr).z();
+            let p = r_in.at(distance / ray_length);
+            let scattered = Ray::new(&p, &Vec3::random_unit_vector(), r_in.time());
+            let weight = 3.0 * self.sigma_s * tr / pdf;
+            return Some(BSDFSample::specular(scattered, weight, Lobe::TRANSMISSION));
+        }
+
+        let tr = transmittance(distance_to_boundary);
+        let weight = 3.0 * tr / (tr.x() + tr.y() + tr.z());
+        Some(self.interface(r_in, rec, 1.0 / self.ior, weight))
+    }
+
+    fn lobes(&self) -> Lobe {
+        Lobe::SPECULAR | Lobe::TRANSMISSION
+    }
+}
@@ -0,0 +1,171 @@
+use crate::hittable::HitRecord;
+use crate::texture::{SolidColor, Texture};
+use crate::utility::color::{cie_xyz, xyz_to_rgb};
+use crate::utility::vec3::*;
+use std::f64::consts::PI;
+use std::ops::{Add, Div, Mul, Sub};
+use std::sync::Arc;
+
+const LAMBDA_MIN: f64 = 380.0;
+const LAMBDA_MAX: f64 = 780.0;
+const LAMBDA_SAMPLES: usize = 41;
+
+// A thin transparent film on top of a surface, like soap or oil.
+// Light reflected at the top and the bottom of the film interferes, so the reflectance depends
+// on the wavelength. It is evaluated over the visible spectrum and integrated to RGB.
+#[derive(Clone)]
+pub struct ThinFilm {
+    pub thickness: Arc<dyn Texture>, //in nanometers
+    pub ior: f64,
+}
+
+impl ThinFilm {
+    pub fn new(thickness: Arc<dyn Texture>, ior: f64) -> Self {
+        Self { thickness, ior }
+    }
+
+    pub fn new_from_value(thickness: f64, ior: f64) -> Self {
+        Self::new(Arc::new(SolidColor::new_from_value(thickness)), ior)
+    }
+
+    pub fn at(&self, rec: &HitRecord) -> FilmLayer {
+        FilmLayer {
+            thickness: self.thickness.scalar_at(rec).max(0.0),
+            ior: self.ior,
+        }
+    }
+}
+
+//the film at one hit point
+#[derive(Debug, Copy, Clone)]
+pub struct FilmLayer {
+    pub thickness: f64,
+    pub ior: f64,
+}
+
+impl FilmLayer {
+    // Reflectance of the film over a substrate of complex index eta + ik, for light arriving
+    // from a medium of index n_incident. eta and k are given at 650nm, 550nm and 450nm like
+    // Conductor's, a dielectric substrate has k = 0.
+    pub fn reflectance(&self, cos_i: f64, n_incident: f64, eta: &Color, k: &Color) -> Color {
+        let cos_i = cos_i.abs().min(1.0);
+        let mut xyz = Vec3::default();
+        let mut white = Vec3::default();
+        for i in 0..LAMBDA_SAMPLES {
+            let lambda =
+                LAMBDA_MIN + (LAMBDA_MAX - LAMBDA_MIN) * i as f64 / (LAMBDA_SAMPLES - 1) as f64;
+            let n_substrate = Complex::new(spectral(eta, lambda), spectral(k, lambda));
+            let cmf = cie_xyz(lambda);
+            xyz += self.airy(lambda, cos_i, n_incident, n_substrate) * cmf;
+            white += cmf;
+        }
+        //scaled so that a flat spectrum keeps its value in every channel
+        let (rgb, white) = (xyz_to_rgb(&xyz), xyz_to_rgb(&white));
+        Color::new(
+            (rgb.x() / white.x()).max(0.0).min(1.0),
+            (rgb.y() / white.y()).max(0.0).min(1.0),
+            (rgb.z() / white.z()).max(0.0).min(1.0),
+        )
+    }
+
+    //unpolarized reflectance at one wavelength, summing every reflection inside the film (Airy)
+    fn airy(&self, lambda: f64, cos_i: f64, n1: f64, n3: Complex) -> f64 {
+        let n1 = Complex::new(n1, 0.0);
+        let n2 = Complex::new(self.ior, 0.0);
+        //n cos(theta) in each medium, by Snell's law, imaginary past the critical angle
+        let sin2 = n1 * n1 * Complex::new(1.0 - cos_i * cos_i, 0.0);
+        let q1 = Complex::new(n1.re * cos_i, 0.0);
+        let q2 = (n2 * n2 - sin2).sqrt();
+        let q3 = (n3 * n3 - sin2).sqrt();
+        //phase difference of one round trip through the film
+        let delta = Complex::new(0.0, 4.0 * PI * self.thickness / lambda) * q2;
+        let phase = delta.exp();
+
+        let rs = |qa: Complex, qb: Complex| (qa - qb) / (qa + qb);
+        let rp = |na: Complex, qa: Complex, nb: Complex, qb: Complex| {
+            (nb * nb * qa - na * na * qb) / (nb * nb * qa + na * na * qb)
+        };
+        let total = |r12: Complex, r23: Complex| {
+            let r23 = r23 * phase;
+            ((r12 + r23) / (Complex::new(1.0, 0.0) + r12 * r23)).norm2()
+        };
+        let s = total(rs(q1, q2), rs(q2, q3));
+        let p = total(rp(n1, q1, n2, q2), rp(n2, q2, n3, q3));
+        (0.5 * (s + p)).min(1.0)
+    }
+}
+
+//linear interpolation of a quantity given at 650nm, 550nm and 450nm
+fn spectral(c: &Color, lambda: f64) -> f64 {
+    if lambda >= 550.0 {
+        let t = ((lambda - 550.0) / 100.0).min(1.0);
+        c.y() + (c.x() - c.y()) * t
+    } else {
+        let t = ((550.0 - lambda) / 100.0).min(1.0);
+        c.y() + (c.z() - c.y()) * t
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    fn norm2(&self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    //principal root, its imaginary part is never negative for the indices used here
+    fn sqrt(&self) -> Self {
+        let r = self.norm2().sqrt();
+        let re = (0.5 * (r + self.re)).max(0.0).sqrt();
+        let im = (0.5 * (r - self.re)).max(0.0).sqrt();
+        Self::new(re, if self.im < 0.0 { -im } else { im })
+    }
+
+    fn exp(&self) -> Self {
+        let r = self.re.exp();
+        Self::new(r * self.im.cos(), r * self.im.sin())
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self {
+        Self::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Self;
+    fn div(self, other: Self) -> Self {
+        let d = other.norm2();
+        Self::new(
+            (self.re * other.re + self.im * other.im) / d,
+            (self.im * other.re - self.re * other.im) / d,
+        )
+    }
+}
@@ -0,0 +1,442 @@
+use crate::material::conductor::Conductor;
+use crate::material::diffuse::OrenNayar;
+use crate::material::layered::Layered;
+use crate::material::mix::{AddMaterial, MixMaterial};
+use crate::material::principled::Principled;
+use crate::material::rough_dielectric::RoughDielectric;
+use crate::material::subsurface::Subsurface;
+use crate::material::*;
+use crate::texture::mipmap::{Filter, WrapMode};
+use crate::texture::perlin::{Noise, Perlin, Simplex, Worley};
+use crate::texture::procedural::*;
+use crate::texture::*;
+use crate::utility::vec3::*;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+
+// Named materials and textures kept in a text file, so scenes and OBJ imports share them:
+//
+//   # comment
+//   texture <name> <kind> key=value ...
+//   material <name> <kind> key=value ...
+//   override <mtl name> <material name>      imported MTL materials of that name use this one
+//
+// Colours are "r,g,b" or one value for all channels. Texture parameters take a colour or
+// "@name" of a texture above. Material parameters take the name of a material above.
+// Ramps are stops "position:colour" joined by "/", such as "0:1/0.5:1,0,0/1:0", or one of
+// the presets wood, marble and granite. Image files are found relative to the library file.
+//
+//   texture  image    file wrap=clamp|repeat|mirror|border border filter=nearest|bilinear|
+//                     trilinear|ewa
+//   texture  solid    color
+//   texture  checker  even odd
+//   texture  noise    scale
+//   texture  marble   ramp scale distortion seed
+//   texture  wood     ramp rings distortion seed
+//   texture  granite  ramp scale roughness seed
+//   texture  fractal  noise=perlin|simplex|worley fractal=fbm|ridged|turbulence scale octaves
+//                     seed
+//   texture  ramp     input ramp
+//   texture  tinted   texture tint
+//
+//   material lambertian        albedo
+//   material oren_nayar        albedo sigma
+//   material metal             albedo fuzz
+//   material dielectric        ior
+//   material rough_dielectric  ior roughness
+//   material conductor         preset=gold|silver|copper|aluminium or eta k, roughness
+//   material principled        base metallic roughness specular specular_tint sheen clearcoat
+//                              clearcoat_gloss transmission ior
+//   material light             emit
+//   material isotropic         albedo
+//   material subsurface        albedo mean_free_path ior
+//   material layered           base ior thickness absorption
+//   material mix               a b weight, or a b fresnel
+//   material add               base emission
+#[derive(Clone, Default)]
+pub struct MaterialLibrary {
+    pub textures: HashMap<String, Arc<dyn Texture>>,
+    pub materials: HashMap<String, Arc<dyn Material>>,
+    pub overrides: HashMap<String, String>,
+}
+
+impl MaterialLibrary {
+    pub fn load(pathname: &str) -> Self {
+        let text = std::fs::read_to_string(pathname).expect("Failed to load material library.");
+        let directory = Path::new(pathname)
+            .parent()
+            .unwrap_or_else(|| Path::new(""));
+        let mut library = Self::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Err(message) = library.parse_line(line, directory) {
+                panic!("{} on line {} of {}", message, number + 1, pathname);
+            }
+        }
+        library
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Material>> {
+        self.materials.get(name).cloned()
+    }
+
+    pub fn material(&self, name: &str) -> Arc<dyn Material> {
+        self.get(name)
+            .unwrap_or_else(|| panic!("No material {:?} in the library", name))
+    }
+
+    pub fn texture(&self, name: &str) -> Arc<dyn Texture> {
+        self.textures
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| panic!("No texture {:?} in the library", name))
+    }
+
+    //the library material an imported MTL material of this name is drawn with, if any
+    pub fn override_for(&self, mtl_name: &str) -> Option<Arc<dyn Material>> {
+        self.overrides.get(mtl_name).and_then(|name| self.get(name))
+    }
+
+    fn parse_line(&mut self, line: &str, directory: &Path) -> Result<(), String> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields[0] {
+            "override" => {
+                if fields.len() != 3 {
+                    return Err("Expected an MTL name and a material".to_string());
+                }
+                if !self.materials.contains_key(fields[2]) {
+                    return Err(format!("Unknown material {:?}", fields[2]));
+                }
+                self.overrides
+                    .insert(fields[1].to_string(), fields[2].to_string());
+                Ok(())
+            }
+            "texture" | "material" => {
+                if fields.len() < 3 {
+                    return Err(format!("Expected a name and a kind of {}", fields[0]));
+                }
+                let params = Params::new(self, &fields[3..])?;
+                if fields[0] == "texture" {
+                    let texture = params.texture_kind(fields[2], directory)?;
+                    params.finish()?;
+                    self.textures.insert(fields[1].to_string(), texture);
+                } else {
+                    let material = params.material_kind(fields[2])?;
+                    params.finish()?;
+                    self.materials.insert(fields[1].to_string(), material);
+                }
+                Ok(())
+            }
+            other => Err(format!("Unknown statement {:?}", other)),
+        }
+    }
+}
+
+//key=value parameters of one line, each read at most once
+struct Params<'a> {
+    library: &'a MaterialLibrary,
+    values: HashMap<&'a str, &'a str>,
+    used: RefCell<HashSet<&'a str>>,
+}
+
+impl<'a> Params<'a> {
+    fn new(library: &'a MaterialLibrary, fields: &[&'a str]) -> Result<Self, String> {
+        let mut values = HashMap::new();
+        for field in fields {
+            let mut parts = field.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) if !key.is_empty() => {
+                    values.insert(key, value);
+                }
+                _ => return Err(format!("Expected key=value, found {:?}", field)),
+            }
+        }
+        Ok(Self {
+            library,
+            values,
+            used: RefCell::new(HashSet::new()),
+        })
+    }
+
+    fn get(&self, key: &'a str) -> Option<&'a str> {
+        self.used.borrow_mut().insert(key);
+        self.values.get(key).copied()
+    }
+
+    fn required(&self, key: &'a str) -> Result<&'a str, String> {
+        self.get(key)
+            .ok_or_else(|| format!("Missing parameter {:?}", key))
+    }
+
+    fn number(&self, key: &'a str, default: f64) -> Result<f64, String> {
+        match self.get(key) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| format!("Bad number {:?} for {:?}", value, key)),
+            None => Ok(default),
+        }
+    }
+
+    fn color(&self, key: &'a str, default: Color) -> Result<Color, String> {
+        match self.get(key) {
+            Some(value) => {
+                parse_color(value).ok_or_else(|| format!("Bad colour {:?} for {:?}", value, key))
+            }
+            None => Ok(default),
+        }
+    }
+
+    fn seed(&self) -> Result<u64, String> {
+        match self.get("seed") {
+            Some(value) => value.parse().map_err(|_| format!("Bad seed {:?}", value)),
+            None => Ok(0),
+        }
+    }
+
+    fn ramp(&self, key: &'a str, default: Ramp) -> Result<Ramp, String> {
+        let value = match self.get(key) {
+            Some(value) => value,
+            None => return Ok(default),
+        };
+        match value {
+            "wood" => return Ok(Ramp::wood()),
+            "marble" => return Ok(Ramp::marble()),
+            "granite" => return Ok(Ramp::granite()),
+            _ => {}
+        }
+        let stops = value
+            .split('/')
+            .map(|stop| {
+                let (position, color) = stop.split_once(':')?;
+                Some((position.trim().parse().ok()?, parse_color(color)?))
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| format!("Bad ramp {:?} for {:?}", value, key))?;
+        Ok(Ramp::new(stops))
+    }
+
+    fn fractal<N: Noise + 'static>(
+        &self,
+        noise: N,
+        fractal: Fractal,
+        scale: f64,
+    ) -> Result<Arc<dyn Texture>, String> {
+        let mut texture = FractalTexture::new(noise, fractal, scale);
+        texture.octaves = self.number("octaves", texture.octaves as f64)? as usize;
+        Ok(Arc::new(texture))
+    }
+
+    fn texture(&self, key: &'a str, default: Color) -> Result<Arc<dyn Texture>, String> {
+        match self.get(key) {
+            Some(value) if value.starts_with('@') => self
+                .library
+                .textures
+                .get(&value[1..])
+                .cloned()
+                .ok_or_else(|| format!("Unknown texture {:?}", &value[1..])),
+            Some(_) => Ok(Arc::new(SolidColor::new(&self.color(key, default)?))),
+            None => Ok(Arc::new(SolidColor::new(&default))),
+        }
+    }
+
+    fn material(&self, key: &'a str) -> Result<Arc<dyn Material>, String> {
+        let name = self.required(key)?;
+        self.library
+            .get(name)
+            .ok_or_else(|| format!("Unknown material {:?}", name))
+    }
+
+    fn finish(&self) -> Result<(), String> {
+        let used = self.used.borrow();
+        match self.values.keys().find(|key| !used.contains(*key)) {
+            Some(key) => Err(format!("Unknown parameter {:?}", key)),
+            None => Ok(()),
+        }
+    }
+
+    fn texture_kind(&self, kind: &str, directory: &Path) -> Result<Arc<dyn Texture>, String> {
+        let texture: Arc<dyn Texture> = match kind {
+            "image" => {
+                let pathname = directory.join(self.required("file")?);
+                let pathname = pathname.to_string_lossy();
+                let image = ImageTexture::load(&pathname)
+                    .map_err(|e| format!("Failed to load {}: {}", pathname, e))?;
+                let wrap = match self.get("wrap") {
+                    None | Some("clamp") => WrapMode::Clamp,
+                    Some("repeat") => WrapMode::Repeat,
+                    Some("mirror") => WrapMode::Mirror,
+                    Some("border") => WrapMode::Border(self.color("border", Color::default())?),
+                    Some(other) => return Err(format!("Unknown wrap mode {:?}", other)),
+                };
+                let filter = match self.get("filter") {
+                    None | Some("trilinear") => Filter::Trilinear,
+                    Some("nearest") => Filter::Nearest,
+                    Some("bilinear") => Filter::Bilinear,
+                    Some("ewa") => Filter::EWA,
+                    Some(other) => return Err(format!("Unknown filter {:?}", other)),
+                };
+                Arc::new(image.with_wrap(wrap).with_filter(filter))
+            }
+            "solid" => self.texture("color", Color::same(0.5))?,
+            "checker" => Arc::new(CheckerTexture::new(
+                self.texture("even", Color::same(0.0))?,
+                self.texture("odd", Color::same(1.0))?,
+            )),
+            "noise" => Arc::new(NoiseTexture::new(self.number("scale", 1.0)?)),
+            "marble" => {
+                let mut marble = MarbleTexture::new(
+                    self.ramp("ramp", Ramp::marble())?,
+                    self.number("scale", 1.0)?,
+                    self.seed()?,
+                );
+                marble.distortion = self.number("distortion", marble.distortion)?;
+                Arc::new(marble)
+            }
+            "wood" => {
+                let mut wood = WoodTexture::new(
+                    self.ramp("ramp", Ramp::wood())?,
+                    self.number("rings", 8.0)?,
+                    self.seed()?,
+                );
+                wood.distortion = self.number("distortion", wood.distortion)?;
+                Arc::new(wood)
+            }
+            "granite" => {
+                let mut granite = GraniteTexture::new(
+                    self.ramp("ramp", Ramp::granite())?,
+                    self.number("scale", 20.0)?,
+                    self.seed()?,
+                );
+                granite.roughness = self.number("roughness", granite.roughness)?;
+                Arc::new(granite)
+            }
+            "fractal" => {
+                let fractal = match self.get("fractal") {
+                    None | Some("fbm") => Fractal::Fbm,
+                    Some("ridged") => Fractal::Ridged,
+                    Some("turbulence") => Fractal::Turbulence,
+                    Some(other) => return Err(format!("Unknown fractal {:?}", other)),
+                };
+                let (scale, seed) = (self.number("scale", 1.0)?, self.seed()?);
+                match self.get("noise") {
+                    None | Some("perlin") => {
+                        self.fractal(Perlin::with_seed(seed), fractal, scale)?
+                    }
+                    Some("simplex") => self.fractal(Simplex::with_seed(seed), fractal, scale)?,
+                    Some("worley") => self.fractal(Worley::with_seed(seed), fractal, scale)?,
+                    Some(other) => return Err(format!("Unknown noise {:?}", other)),
+                }
+            }
+            "ramp" => Arc::new(ColorRamp::new(
+                self.texture("input", Color::same(0.5))?,
+                self.ramp(
+                    "ramp",
+                    Ramp::new(vec![(0.0, Color::same(0.0)), (1.0, Color::same(1.0))]),
+                )?,
+            )),
+            "tinted" => Arc::new(TintedTexture::new(
+                self.texture("texture", Color::same(1.0))?,
+                &self.color("tint", Color::same(1.0))?,
+            )),
+            other => return Err(format!("Unknown kind of texture {:?}", other)),
+        };
+        Ok(texture)
+    }
+
+    fn material_kind(&self, kind: &str) -> Result<Arc<dyn Material>, String> {
+        let material: Arc<dyn Material> = match kind {
+            "lambertian" => Arc::new(Lambertian::new(self.texture("albedo", Color::same(0.5))?)),
+            "oren_nayar" => Arc::new(OrenNayar::new(
+                self.texture("albedo", Color::same(0.5))?,
+                self.number("sigma", 20.0)?,
+            )),
+            "metal" => Arc::new(Metal::new(
+                &self.color("albedo", Color::same(0.9))?,
+                self.number("fuzz", 0.0)?,
+            )),
+            "dielectric" => Arc::new(Dielectric::new(self.number("ior", 1.5)?)),
+            "rough_dielectric" => Arc::new(RoughDielectric::new(
+                self.number("ior", 1.5)?,
+                self.texture("roughness", Color::same(0.1))?,
+            )),
+            "conductor" => {
+                let mut conductor = match self.get("preset") {
+                    Some("gold") => Conductor::gold(),
+                    Some("silver") => Conductor::silver(),
+                    Some("copper") => Conductor::copper(),
+                    Some("aluminium") => Conductor::aluminium(),
+                    Some(other) => return Err(format!("Unknown conductor {:?}", other)),
+                    None => Conductor::new(
+                        &self.color("eta", Color::same(1.5))?,
+                        &self.color("k", Color::same(3.0))?,
+                        0.2,
+                    ),
+                };
+                conductor.roughness = self.number("roughness", conductor.roughness)?;
+                Arc::new(conductor)
+            }
+            "principled" => {
+                let mut mat = Principled::new(self.texture("base", Color::same(0.8))?);
+                mat.metallic = self.texture("metallic", Color::same(0.0))?;
+                mat.roughness = self.texture("roughness", Color::same(0.5))?;
+                mat.specular = self.texture("specular", Color::same(0.5))?;
+                mat.specular_tint = self.texture("specular_tint", Color::same(0.0))?;
+                mat.sheen = self.texture("sheen", Color::same(0.0))?;
+                mat.clearcoat = self.texture("clearcoat", Color::same(0.0))?;
+                mat.clearcoat_gloss = self.texture("clearcoat_gloss", Color::same(1.0))?;
+                mat.transmission = self.texture("transmission", Color::same(0.0))?;
+                mat.ior = self.texture("ior", Color::same(1.5))?;
+                Arc::new(mat)
+            }
+            "light" => Arc::new(DiffuseLight::new(self.texture("emit", Color::same(1.0))?)),
+            "isotropic" => Arc::new(Isotropic::new(self.texture("albedo", Color::same(0.5))?)),
+            "subsurface" => Arc::new(Subsurface::from_albedo(
+                &self.color("albedo", Color::same(0.8))?,
+                &self.color("mean_free_path", Color::same(1.0))?,
+                self.number("ior", 1.3)?,
+            )),
+            "layered" => Arc::new(Layered::new(
+                self.material("base")?,
+                self.number("ior", 1.5)?,
+                self.number("thickness", 0.0)?,
+                &self.color("absorption", Color::default())?,
+            )),
+            "mix" => {
+                let (a, b) = (self.material("a")?, self.material("b")?);
+                match self.get("fresnel") {
+                    Some(_) => Arc::new(MixMaterial::fresnel(a, b, self.number("fresnel", 1.5)?)),
+                    None => Arc::new(MixMaterial::new(
+                        a,
+                        b,
+                        self.texture("weight", Color::same(0.5))?,
+                    )),
+                }
+            }
+            "add" => Arc::new(AddMaterial::new(
+                self.material("base")?,
+                self.material("emission")?,
+            )),
+            other => return Err(format!("Unknown kind of material {:?}", other)),
+        };
+        Ok(material)
+    }
+}
+
+//one value for all channels or three separated by commas
+fn parse_color(value: &str) -> Option<Color> {
+    let values: Vec<f64> = value
+        .split(',')
+        .map(|s| s.trim().parse())
+        .collect::<Result<_, _>>()
+        .ok()?;
+    match values.len() {
+        1 => Some(Color::same(values[0])),
+        3 => Some(Color::new(values[0], values[1], values[2])),
+        _ => None,
+    }
+}
@@ -1,11 +1,97 @@
+use crate::hittable::alpha_cutout::AlphaCutout;
 use crate::hittable::bvh::BVHNode;
 use crate::hittable::triangle::Triangle;
 use crate::hittable::HittableList;
+use crate::material::mix::AddMaterial;
+use crate::material::normal_map::{BumpMap, NormalMap};
+use crate::material::principled::Principled;
+use crate::material::rough_dielectric::RoughDielectric;
 use crate::material::*;
-use crate::texture::ImageTexture;
+use crate::material_library::MaterialLibrary;
+use crate::texture::mipmap::{Filter, WrapMode};
+use crate::texture::{ImageTexture, SolidColor, Texture, TintedTexture};
 use crate::utility::vec3::*;
-use crate::{TIME0, TIME1};
-use tobj::{load_obj, LoadOptions};
+use console::style;
+use std::sync::Arc;
+use tobj::{load_obj, LoadOptions, Mesh};
+
+const ALPHA_THRESHOLD: f64 = 0.5; //cutout of map_d and texture alpha
+const MTL_BUMP_SCALE: f64 = 0.01; //height change per unit of uv for a bump map value of 1
+
+//triangles of one mesh, smooth shaded when the file has vertex normals
+fn mesh_triangles<M: Material + Clone + 'static>(mesh: &Mesh, scale: &Vec3, mat: M) -> BVHNode {
+    let positions = &mesh.positions; //points position
+    let indices = &mesh.indices; //points index (maybe joint)
+    let texcoords = &mesh.texcoords;
+    let texcoord_indices = &mesh.texcoord_indices;
+    let has_normals = !mesh.normals.is_empty() && !mesh.normal_indices.is_empty();
+    let mut points = Vec::new();
+    for i in (0..positions.len()).step_by(3) {
+        points.push(Point3::new(positions[i], positions[i + 1], positions[i + 2]) * *scale);
+    }
+
+    // Tangents along u are summed over the faces around each vertex, weighted by area.
+    let mut faces = Vec::new();
+    let mut tangents = vec![Vec3::default(); points.len()];
+    for i in (0..indices.len() - indices.len() % 3).step_by(3) {
+        let mut uv = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)];
+        if !texcoords.is_empty() {
+            for j in 0..3 {
+                let index = texcoord_indices[i + j] as usize;
+                uv[j] = (texcoords[index << 1], texcoords[index << 1 | 1]);
+            }
+        }
+        let corners = [
+            indices[i] as usize,
+            indices[i + 1] as usize,
+            indices[i + 2] as usize,
+        ];
+        let mut triangle = Triangle::new(
+            &points[corners[0]],
+            &points[corners[1]],
+            &points[corners[2]],
+            mat.clone(),
+            uv[0],
+            uv[1],
+            uv[2],
+        );
+        if has_normals {
+            //normals scale inversely to keep perpendicular to the scaled surface
+            let normal = |j: usize| {
+                let index = mesh.normal_indices[i + j] as usize * 3;
+                let n = &mesh.normals;
+                Vec3::new(
+                    n[index] / scale.x(),
+                    n[index + 1] / scale.y(),
+                    n[index + 2] / scale.z(),
+                )
+            };
+            triangle = triangle.with_normals(&normal(0), &normal(1), &normal(2));
+        }
+        for &corner in corners.iter() {
+            tangents[corner] += triangle.tangents[0] * triangle.area();
+        }
+        faces.push((corners, triangle));
+    }
+
+    let mut triangles = HittableList::new();
+    for (corners, triangle) in faces {
+        let tangent = |corner: usize| {
+            if tangents[corner].length_squared() > 1e-24 {
+                tangents[corner].unit()
+            } else {
+                triangle.tangents[0]
+            }
+        };
+        let (ta, tb, tc) = (
+            tangent(corners[0]),
+            tangent(corners[1]),
+            tangent(corners[2]),
+        );
+        triangles.add(Box::new(triangle.with_tangents(&ta, &tb, &tc)));
+    }
+    BVHNode::new(triangles, 0.0, 0.0)
+}
 
 pub fn load_naive<M: Material + Clone + 'static>(
     pathname: &str,
@@ -24,34 +110,11 @@ pub fn load_naive<M: Material + Clone + 'static>(
     .expect("Failed to load .obj file.");
     let mut objects = HittableList::new();
     for m in models {
-        let positions = &m.mesh.positions; //points position
-        let indices = &m.mesh.indices; //points index (maybe joint)
-        let texcoords = &m.mesh.texcoords;
-        let texcoord_indices = &m.mesh.texcoord_indices;
-        let mut points = Vec::new();
-        let mut triangles = HittableList::new();
-        for i in (0..positions.len()).step_by(3) {
-            points.push(Point3::new(positions[i], positions[i + 1], positions[i + 2]) * scale);
-        }
-        for i in (0..indices.len() - indices.len() % 3).step_by(3) {
-            let mut uv = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)];
-            if !texcoords.is_empty() {
-                for j in 0..3 {
-                    let index = texcoord_indices[i + j] as usize;
-                    uv[j] = (texcoords[index << 1], texcoords[index << 1 | 1]);
-                }
-            }
-            triangles.add(Box::new(Triangle::new(
-                &points[indices[i] as usize],
-                &points[indices[i + 1] as usize],
-                &points[indices[i + 2] as usize],
-                mat.clone(),
-                uv[0],
-                uv[1],
-                uv[2],
-            )));
-        }
-        objects.add(Box::new(BVHNode::new(triangles, TIME0, TIME1)));
+        objects.add(Box::new(mesh_triangles(
+            &m.mesh,
+            &Vec3::same(scale),
+            mat.clone(),
+        )));
     }
     if objects.size() >= 6 {
         HittableList::bvh(objects)
@@ -60,12 +123,29 @@ pub fn load_naive<M: Material + Clone + 'static>(
     }
 }
 
+// Materials come from the .mtl file next to the model. Meshes without one, or naming one the
+// file does not have, are drawn diffuse in default_color. Maps that fail to load are skipped.
 pub fn load_pro(project_name: &str, scale: Vec3, default_color: &Color) -> HittableList {
-    // must have a .mtl file
+    load_pro_with_library(
+        project_name,
+        scale,
+        default_color,
+        &MaterialLibrary::default(),
+    )
+}
+
+// As load_pro, but MTL materials named in the overrides of the library are replaced by library
+// materials. Their normal maps and cutouts still apply.
+pub fn load_pro_with_library(
+    project_name: &str,
+    scale: Vec3,
+    default_color: &Color,
+    library: &MaterialLibrary,
+) -> HittableList {
     let path_prefix = format!("objects/{}/", project_name);
     let pathname = format!("{}{}.obj", path_prefix, project_name);
     let (models, materials) = load_obj(
-        pathname,
+        &pathname,
         &LoadOptions {
             single_index: false,
             triangulate: true,
@@ -75,72 +155,365 @@ pub fn load_pro(project_name: &str, scale: Vec3, default_color: &Color) -> Hitta
     )
     .expect("Failed to load OBJ file.");
 
-    // read mtl texture
-    let materials = materials.expect("Failed to load MTL file");
-    let mut textures: Vec<ImageTexture> = Vec::new();
-    for mtl in materials {
-        if let Some(texture_name) = mtl.diffuse_texture {
-            let pathname = format!("{}{}", path_prefix, texture_name);
-            let tex = ImageTexture::new(&pathname);
-            textures.push(tex);
-        } else {
-            textures.push(ImageTexture::default());
-        }
-    }
-    let default_mtl = Lambertian::new_from_color(default_color);
+    let materials = materials.unwrap_or_else(|e| {
+        warn(&format!("No materials for {}: {}", pathname, e));
+        Vec::new()
+    });
+    let surfaces: Vec<MtlSurface> = materials
+        .iter()
+        .map(|mtl| MtlSurface::new(mtl, &path_prefix, default_color, library))
+        .collect();
+    let default_surface = MtlSurface::plain(default_color);
 
     let mut objects = HittableList::new();
     for m in models {
-        let positions = &m.mesh.positions; //points position
-        let indices = &m.mesh.indices; //points index (maybe joint)
-        let texcoords = &m.mesh.texcoords;
-        let texcoord_indices = &m.mesh.texcoord_indices;
-        let mut points = Vec::new();
-        let mut triangles = HittableList::new();
-        for i in (0..positions.len()).step_by(3) {
-            points.push(Point3::new(positions[i], positions[i + 1], positions[i + 2]) * scale);
+        let surface = match m.mesh.material_id {
+            Some(id) if id < surfaces.len() => &surfaces[id],
+            Some(id) => {
+                warn(&format!(
+                    "Mesh {} of {} uses missing material {}",
+                    m.name, pathname, id
+                ));
+                &default_surface
+            }
+            None => &default_surface,
+        };
+        surface.add_mesh(&mut objects, &m.mesh, &scale);
+    }
+    if objects.size() >= 6 {
+        HittableList::bvh(objects)
+    } else {
+        objects
+    }
+}
+
+fn warn(message: &str) {
+    println!("{}", style(message).yellow());
+}
+
+// What an .mtl material is drawn as:
+//   library override     the library material, keeping the maps of the surface detail and d
+//   illum 4, 6, 7, 9     glass of index Ni, frosted unless Ns is high or missing
+//   illum 3, 5           metal of colour Ks
+//   d below 1            principled with that much transmission
+//   Ks above 0           principled plastic with specular Ks
+//   otherwise            diffuse
+// The colour is Kd times map_Kd, Ns and map_Ns give the roughness, Ke and map_Ke add emission,
+// map_d cuts the surface out and norm, bump or map_Bump perturb the normal.
+#[derive(Clone)]
+struct MtlSurface {
+    kind: MtlKind,
+    emission: Option<Arc<dyn Texture>>,
+    detail: SurfaceDetail,
+    opacity: Option<ImageTexture>,
+}
+
+#[derive(Clone)]
+enum MtlKind {
+    Diffuse(Arc<dyn Texture>),
+    Glossy(Principled),
+    Glass(RoughDielectric<MtlRoughness>),
+    Library(Arc<dyn Material>),
+}
+
+impl MtlSurface {
+    fn plain(color: &Color) -> Self {
+        Self {
+            kind: MtlKind::Diffuse(Arc::new(SolidColor::new(color))),
+            emission: None,
+            detail: SurfaceDetail::Smooth,
+            opacity: None,
+        }
+    }
+
+    fn new(
+        mtl: &tobj::Material,
+        path_prefix: &str,
+        default_color: &Color,
+        library: &MaterialLibrary,
+    ) -> Self {
+        if let Some(mat) = library.override_for(&mtl.name) {
+            let diffuse_map = mtl
+                .diffuse_texture
+                .as_ref()
+                .and_then(|entry| load_map(entry, path_prefix));
+            return Self {
+                kind: MtlKind::Library(mat),
+                emission: None,
+                detail: surface_detail(mtl, path_prefix),
+                opacity: opacity(mtl, path_prefix, &diffuse_map),
+            };
         }
 
-        let tex = &textures[m.mesh.material_id.unwrap()];
-        let default = tex.empty();
-        let mtl = Lambertian::new(tex.clone());
-
-        for i in (0..indices.len() - indices.len() % 3).step_by(3) {
-            let mut uv = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)];
-            if !texcoords.is_empty() {
-                for j in 0..3 {
-                    let index = texcoord_indices[i + j] as usize;
-                    uv[j] = (texcoords[index << 1], texcoords[index << 1 | 1]);
-                }
+        let map = |entry: &Option<String>| {
+            entry
+                .as_ref()
+                .and_then(|entry| load_map(entry, path_prefix))
+        };
+        let tinted = |map: Option<ImageTexture>, tint: Option<Color>, default: Color| {
+            let texture: Arc<dyn Texture> = match map {
+                Some(map) => Arc::new(TintedTexture::new(
+                    map,
+                    &tint.unwrap_or_else(|| Color::same(1.0)),
+                )),
+                None => Arc::new(SolidColor::new(&tint.unwrap_or(default))),
+            };
+            texture
+        };
+
+        let diffuse_map = map(&mtl.diffuse_texture);
+        let opacity = opacity(mtl, path_prefix, &diffuse_map);
+        let base_color = tinted(diffuse_map, mtl.diffuse.map(to_color), *default_color);
+
+        let specular_map = map(&mtl.specular_texture);
+        let has_specular = specular_map.is_some()
+            || mtl
+                .specular
+                .map_or(false, |ks| to_color(ks).length_squared() > 0.0);
+        let specular = tinted(specular_map, mtl.specular.map(to_color), Color::default());
+        let roughness = |default_shininess: f64| MtlRoughness {
+            shininess: mtl.shininess.unwrap_or(default_shininess),
+            map: map(&mtl.shininess_texture),
+        };
+        let dissolve = mtl.dissolve.unwrap_or(1.0);
+        let ior = mtl.optical_density.unwrap_or(1.5);
+
+        let principled = |base_color: Arc<dyn Texture>| {
+            let mut mat = Principled::new(base_color);
+            mat.roughness = Arc::new(roughness(0.0));
+            mat.ior = Arc::new(SolidColor::new_from_value(ior));
+            mat
+        };
+        let kind = match mtl.illumination_model {
+            Some(4) | Some(6) | Some(7) | Some(9) => {
+                MtlKind::Glass(RoughDielectric::new(ior, roughness(f64::INFINITY)))
             }
-            if default {
-                triangles.add(Box::new(Triangle::new(
-                    &points[indices[i] as usize],
-                    &points[indices[i + 1] as usize],
-                    &points[indices[i + 2] as usize],
-                    default_mtl.clone(),
-                    uv[0],
-                    uv[1],
-                    uv[2],
-                )));
-            } else {
-                triangles.add(Box::new(Triangle::new(
-                    &points[indices[i] as usize],
-                    &points[indices[i + 1] as usize],
-                    &points[indices[i + 2] as usize],
-                    mtl.clone(),
-                    uv[0],
-                    uv[1],
-                    uv[2],
-                )));
+            Some(3) | Some(5) => {
+                let mut mat = principled(specular);
+                mat.metallic = Arc::new(SolidColor::new_from_value(1.0));
+                MtlKind::Glossy(mat)
+            }
+            _ if dissolve < 1.0 => {
+                let mut mat = principled(base_color);
+                mat.transmission = Arc::new(SolidColor::new_from_value(1.0 - dissolve));
+                MtlKind::Glossy(mat)
+            }
+            Some(0) | Some(1) => MtlKind::Diffuse(base_color),
+            _ if has_specular => {
+                let mut mat = principled(base_color);
+                mat.specular = specular;
+                MtlKind::Glossy(mat)
+            }
+            _ => MtlKind::Diffuse(base_color),
+        };
+
+        let ke = mtl
+            .unknown_param
+            .get("Ke")
+            .and_then(|entry| parse_color(entry));
+        let emission_map = map(&mtl.unknown_param.get("map_Ke").cloned());
+        let emission = if emission_map.is_some() || ke.map_or(false, |ke| ke.length_squared() > 0.0)
+        {
+            Some(tinted(emission_map, ke, Color::default()))
+        } else {
+            None
+        };
+
+        Self {
+            kind,
+            emission,
+            detail: surface_detail(mtl, path_prefix),
+            opacity,
+        }
+    }
+
+    fn add_mesh(&self, objects: &mut HittableList, mesh: &Mesh, scale: &Vec3) {
+        match &self.kind {
+            MtlKind::Diffuse(albedo) => {
+                self.add_emissive(objects, mesh, scale, Lambertian::new(albedo.clone()))
             }
+            MtlKind::Glossy(mat) => self.add_emissive(objects, mesh, scale, mat.clone()),
+            MtlKind::Glass(mat) => self.add_emissive(objects, mesh, scale, mat.clone()),
+            MtlKind::Library(mat) => self.add_emissive(objects, mesh, scale, mat.clone()),
         }
-        objects.add(Box::new(BVHNode::new(triangles, TIME0, TIME1)));
     }
-    if objects.size() >= 6 {
-        HittableList::bvh(objects)
+
+    fn add_emissive<M: Material + Clone + 'static>(
+        &self,
+        objects: &mut HittableList,
+        mesh: &Mesh,
+        scale: &Vec3,
+        mat: M,
+    ) {
+        match &self.emission {
+            Some(emission) => {
+                let mat = AddMaterial::new(mat, DiffuseLight::new(emission.clone()));
+                add_mesh(objects, mesh, scale, mat, &self.detail, &self.opacity)
+            }
+            None => add_mesh(objects, mesh, scale, mat, &self.detail, &self.opacity),
+        }
+    }
+}
+
+// Roughness from a Phong exponent, matching the width of a Beckmann lobe: alpha^2 = 2 / (Ns + 2).
+// map_Ns scales Ns.
+#[derive(Clone)]
+struct MtlRoughness {
+    shininess: f64,
+    map: Option<ImageTexture>,
+}
+
+impl Texture for MtlRoughness {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let shininess = match &self.map {
+            Some(map) => self.shininess * map.scalar(u, v, p),
+            None => self.shininess,
+        };
+        let alpha = (2.0 / (shininess.max(0.0) + 2.0)).sqrt();
+        Color::same(alpha.sqrt())
+    }
+}
+
+//map_d wins over the alpha of the diffuse texture
+fn opacity(
+    mtl: &tobj::Material,
+    path_prefix: &str,
+    diffuse_map: &Option<ImageTexture>,
+) -> Option<ImageTexture> {
+    let dissolve_map = mtl
+        .dissolve_texture
+        .as_ref()
+        .and_then(|entry| load_map(entry, path_prefix));
+    match dissolve_map {
+        Some(opacity) => Some(opacity),
+        None => diffuse_map
+            .as_ref()
+            .filter(|map| map.has_alpha())
+            .map(|map| map.alpha_channel()),
+    }
+}
+
+fn to_color(c: [f64; 3]) -> Color {
+    Color::new(c[0], c[1], c[2])
+}
+
+//one value for all channels or three
+fn parse_color(entry: &str) -> Option<Color> {
+    let values: Vec<f64> = entry
+        .split_whitespace()
+        .map(|s| s.parse())
+        .collect::<Result<_, _>>()
+        .ok()?;
+    match values.len() {
+        1 => Some(Color::same(values[0])),
+        3 => Some(Color::new(values[0], values[1], values[2])),
+        _ => None,
+    }
+}
+
+// Maps repeat unless the entry has "-clamp on", and are filtered anisotropically since models
+// are often seen at grazing angles, like the ground or the sea.
+fn load_map(entry: &str, path_prefix: &str) -> Option<ImageTexture> {
+    let (texture_name, _) = parse_map(entry);
+    let pathname = format!("{}{}", path_prefix, texture_name);
+    let tokens: Vec<&str> = entry.split_whitespace().collect();
+    let clamp = tokens.windows(2).any(|w| w[0] == "-clamp" && w[1] == "on");
+    let wrap = if clamp {
+        WrapMode::Clamp
     } else {
-        objects
+        WrapMode::Repeat
+    };
+    match ImageTexture::load(&pathname) {
+        Ok(map) => Some(map.with_wrap(wrap).with_filter(Filter::EWA)),
+        Err(e) => {
+            warn(&format!("Skipping texture {}: {}", pathname, e));
+            None
+        }
+    }
+}
+
+#[derive(Clone)]
+enum SurfaceDetail {
+    Smooth,
+    Normal(ImageTexture, f64), //tangent space normal map and its strength
+    Bump(ImageTexture, f64),   //height map and its scale
+}
+
+// "norm" is a normal map. "bump" and "map_Bump" should hold a height map, but many exporters
+// write normal maps there, so those are recognised by their mostly blue colour.
+fn surface_detail(mtl: &tobj::Material, path_prefix: &str) -> SurfaceDetail {
+    if let Some(entry) = mtl.unknown_param.get("norm") {
+        let (_, multiplier) = parse_map(entry);
+        if let Some(map) = load_map(entry, path_prefix) {
+            return SurfaceDetail::Normal(map, multiplier);
+        }
+    }
+    if let Some(entry) = &mtl.normal_texture {
+        let (_, multiplier) = parse_map(entry);
+        if let Some(map) = load_map(entry, path_prefix) {
+            return if looks_like_normal_map(&map) {
+                SurfaceDetail::Normal(map, multiplier)
+            } else {
+                SurfaceDetail::Bump(map, multiplier * MTL_BUMP_SCALE)
+            };
+        }
+    }
+    SurfaceDetail::Smooth
+}
+
+//file name and -bm multiplier of a map entry, the options come before the name
+fn parse_map(entry: &str) -> (String, f64) {
+    let tokens: Vec<&str> = entry.split_whitespace().collect();
+    let mut multiplier = 1.0;
+    for i in 0..tokens.len().saturating_sub(1) {
+        if tokens[i] == "-bm" {
+            multiplier = tokens[i + 1].parse().unwrap_or(1.0);
+        }
+    }
+    let texture_name = tokens.last().map_or(String::new(), |name| name.to_string());
+    (texture_name, multiplier)
+}
+
+fn looks_like_normal_map(map: &ImageTexture) -> bool {
+    let mut sum = Color::default();
+    let n = 16;
+    for i in 0..n {
+        for j in 0..n {
+            let (u, v) = ((i as f64 + 0.5) / n as f64, (j as f64 + 0.5) / n as f64);
+            sum += map.value(u, v, &Point3::default());
+        }
+    }
+    let average = sum / (n * n) as f64;
+    average.z() > 0.75 && (average.x() - 0.5).abs() < 0.15 && (average.y() - 0.5).abs() < 0.15
+}
+
+fn add_mesh<M: Material + Clone + 'static>(
+    objects: &mut HittableList,
+    mesh: &Mesh,
+    scale: &Vec3,
+    mat: M,
+    detail: &SurfaceDetail,
+    opacity: &Option<ImageTexture>,
+) {
+    let node = match detail {
+        SurfaceDetail::Smooth => mesh_triangles(mesh, scale, mat),
+        SurfaceDetail::Normal(map, strength) => {
+            let mut mat = NormalMap::new(mat, map.clone());
+            mat.strength = *strength;
+            mesh_triangles(mesh, scale, mat)
+        }
+        SurfaceDetail::Bump(map, bump_scale) => {
+            let mat = BumpMap::new(mat, map.clone(), *bump_scale);
+            mesh_triangles(mesh, scale, mat)
+        }
+    };
+    match opacity {
+        Some(opacity) => objects.add(Box::new(AlphaCutout::new(
+            node,
+            opacity.clone(),
+            ALPHA_THRESHOLD,
+        ))),
+        None => objects.add(Box::new(node)),
     }
 }
 
@@ -0,0 +1,106 @@
+// Piecewise constant densities over [0, 1) and [0, 1)^2, proportional to tabulated values,
+// sampled by inverting their cumulative distributions.
+
+#[derive(Debug, Clone, Default)]
+pub struct Distribution1D {
+    pub func: Vec<f64>,
+    cdf: Vec<f64>,
+    pub integral: f64,
+}
+
+impl Distribution1D {
+    pub fn new(func: Vec<f64>) -> Self {
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i].abs() / n as f64;
+        }
+        let integral = cdf[n];
+        for (i, c) in cdf.iter_mut().enumerate().skip(1) {
+            //all zero falls back to uniform
+            *c = if integral > 0.0 {
+                *c / integral
+            } else {
+                i as f64 / n as f64
+            };
+        }
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    //x in [0, 1) for a uniform u, with its density and the segment it falls in
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        //last segment whose cdf starts at or below u
+        let offset = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .saturating_sub(1)
+            .min(self.count() - 1);
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        let du = if width > 0.0 {
+            (u - self.cdf[offset]) / width
+        } else {
+            0.0
+        };
+        let x = ((offset as f64 + du) / self.count() as f64).min(1.0 - f64::EPSILON);
+        (x, self.pdf(offset), offset)
+    }
+
+    pub fn pdf(&self, offset: usize) -> f64 {
+        if self.integral > 0.0 {
+            self.func[offset].abs() / self.integral
+        } else {
+            1.0
+        }
+    }
+
+    fn offset(&self, x: f64) -> usize {
+        ((x * self.count() as f64) as usize).min(self.count() - 1)
+    }
+}
+
+// Values are given row by row, nu to a row and nv rows. A row is picked by the marginal
+// density of the row sums, then a column by the density of that row.
+#[derive(Debug, Clone, Default)]
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(func: &[f64], nu: usize, nv: usize) -> Self {
+        let conditional: Vec<Distribution1D> = func
+            .chunks(nu)
+            .take(nv)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|d| d.integral).collect());
+        Self {
+            conditional,
+            marginal,
+        }
+    }
+
+    //(u, v) in [0, 1)^2 for two uniform numbers, and its density
+    pub fn sample(&self, u0: f64, u1: f64) -> ((f64, f64), f64) {
+        let (v, pdf_v, row) = self.marginal.sample(u1);
+        let (u, pdf_u, _) = self.conditional[row].sample(u0);
+        ((u, v), pdf_u * pdf_v)
+    }
+
+    pub fn pdf(&self, u: f64, v: f64) -> f64 {
+        let row = &self.conditional[self.marginal.offset(v)];
+        if self.marginal.integral > 0.0 {
+            row.func[row.offset(u)].abs() / self.marginal.integral
+        } else {
+            1.0
+        }
+    }
+}
@@ -1,3 +1,4 @@
+pub mod distribution;
 pub mod onb;
 
 use crate::hittable::Hittable;
@@ -62,18 +63,18 @@ impl<'a, H: Hittable> PDF for HittablePDF<'a, H> {
 }
 
 #[derive(Clone)]
-pub struct MixturePDF<'a, P0: PDF, P1: PDF> {
+pub struct MixturePDF<'a, P0: PDF + ?Sized, P1: PDF + ?Sized> {
     p0: &'a P0,
     p1: &'a P1,
 }
 
-impl<'a, P0: PDF, P1: PDF> MixturePDF<'a, P0, P1> {
+impl<'a, P0: PDF + ?Sized, P1: PDF + ?Sized> MixturePDF<'a, P0, P1> {
     pub fn new(p0: &'a P0, p1: &'a P1) -> Self {
         Self { p0, p1 }
     }
 }
 
-impl<'a, P0: PDF, P1: PDF> PDF for MixturePDF<'a, P0, P1> {
+impl<'a, P0: PDF + ?Sized, P1: PDF + ?Sized> PDF for MixturePDF<'a, P0, P1> {
     fn value(&self, direction: &Vec3) -> f64 {
         0.5 * (self.p0.value(direction) + self.p1.value(direction))
     }
@@ -19,6 +19,18 @@ impl ONB {
         Self { axis: [v0, v1, v2] }
     }
 
+    //w along the normal and u along a tangent, which need not be exactly perpendicular to it
+    pub fn build_from_wu(w: &Vec3, u: &Vec3) -> Self {
+        let v2 = w.unit();
+        let v0 = *u - dot(u, &v2) * v2;
+        if v0.length_squared() < 1e-12 {
+            return Self::build_from_w(w);
+        }
+        let v0 = v0.unit();
+        let v1 = cross(&v2, &v0);
+        Self { axis: [v0, v1, v2] }
+    }
+
     pub fn u(&self) -> Vec3 {
         self.axis[0]
     }
@@ -38,6 +50,15 @@ impl ONB {
     pub fn local_vec(&self, a: &Vec3) -> Vec3 {
         a.x() * self.axis[0] + a.y() * self.axis[1] + a.z() * self.axis[2]
     }
+
+    //coordinates of a world vector in this basis
+    pub fn to_local(&self, a: &Vec3) -> Vec3 {
+        Vec3::new(
+            dot(a, &self.axis[0]),
+            dot(a, &self.axis[1]),
+            dot(a, &self.axis[2]),
+        )
+    }
 }
 
 impl Index<usize> for ONB {
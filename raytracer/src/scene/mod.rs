@@ -1,22 +1,28 @@
 pub mod my_scene;
 
+use crate::camera::exposure::CameraSettings;
 use crate::hittable::aarect::*;
 use crate::hittable::bvh::BVHNode;
 use crate::hittable::constant_medium::*;
 use crate::hittable::mybox::*;
 use crate::hittable::sphere::*;
 use crate::hittable::{FlipFace, HittableList, RotateY, Translate};
+use crate::material::phase::HenyeyGreenstein;
 use crate::material::{Dielectric, DiffuseLight, Lambertian, Metal};
+use crate::material_library::MaterialLibrary;
 use crate::obj_loader::*;
 use crate::texture::*;
 use crate::utility::vec3::*;
 use crate::utility::*;
-use crate::{TIME0, TIME1};
 use raytracer_codegen::impl_static_final_scene;
 
 impl_static_final_scene!();
 
+pub const MATERIAL_LIBRARY: &str = "raytracer/sources/materials.txt";
+
 pub fn random_scene() -> HittableList {
+    //the spheres bounce up while the shutter of a default camera is open, in seconds
+    let shutter = CameraSettings::default().shutter_time;
     let mut world = HittableList::default();
     let checker =
         CheckerTexture::new_from_color(&Color::new(0.2, 0.3, 0.1), &Color::new(0.9, 0.9, 0.9));
@@ -48,7 +54,7 @@ pub fn random_scene() -> HittableList {
                         &center2,
                         0.2,
                         0.0,
-                        1.0,
+                        shutter,
                         sphere_material,
                     )));
                 } else if choose_mat < 0.95 {
@@ -92,7 +98,7 @@ pub fn random_scene() -> HittableList {
     //     DiffuseLight::new_from_color(&Color::new(7.0, 7.0, 7.0)),
     // )));
     //
-    world.add(Box::new(BVHNode::new(list, TIME0, TIME1)));
+    world.add(Box::new(BVHNode::new(list, 0.0, shutter)));
     world
 }
 
@@ -174,10 +180,11 @@ pub fn simple_light() -> HittableList {
 
 pub fn cornell_box() -> HittableList {
     let mut objects = HittableList::default();
-    let red = Lambertian::new_from_color(&Color::new(0.65, 0.05, 0.05));
-    let white = Lambertian::new_from_color(&Color::new(0.73, 0.73, 0.73));
-    let green = Lambertian::new_from_color(&Color::new(0.12, 0.45, 0.15));
-    let light = DiffuseLight::new_from_color(&Color::new(15.0, 15.0, 15.0));
+    let library = MaterialLibrary::load(MATERIAL_LIBRARY);
+    let red = library.material("cornell_red");
+    let white = library.material("cornell_white");
+    let green = library.material("cornell_green");
+    let light = library.material("cornell_light");
     objects.add(Box::new(YZRect::new(0., 555., 0., 555., 555., green)));
     objects.add(Box::new(YZRect::new(0., 555., 0., 555., 0., red)));
     //flip light
@@ -213,7 +220,7 @@ pub fn cornell_box() -> HittableList {
     let box1 = Box::new(Translate::new(box1, &Vec3::new(265., 0., 295.)));
     objects.add(box1);
 
-    let glass = Dielectric::new(1.5);
+    let glass = library.material("glass");
     objects.add(Box::new(Sphere::new(
         &Point3::new(190., 90., 190.),
         90.,
@@ -225,10 +232,11 @@ pub fn cornell_box() -> HittableList {
 
 pub fn cornell_smoke() -> HittableList {
     let mut objects = HittableList::default();
-    let red = Lambertian::new_from_color(&Color::new(0.65, 0.05, 0.05));
-    let white = Lambertian::new_from_color(&Color::new(0.73, 0.73, 0.73));
-    let green = Lambertian::new_from_color(&Color::new(0.12, 0.45, 0.15));
-    let light = DiffuseLight::new_from_color(&Color::new(7.0, 7.0, 7.0));
+    let library = MaterialLibrary::load(MATERIAL_LIBRARY);
+    let red = library.material("cornell_red");
+    let white = library.material("cornell_white");
+    let green = library.material("cornell_green");
+    let light = library.material("dim_light");
     objects.add(Box::new(YZRect::new(0., 555., 0., 555., 555., green)));
     objects.add(Box::new(YZRect::new(0., 555., 0., 555., 0., red)));
     objects.add(Box::new(XZRect::new(113., 343., 127., 432., 554., light)));
@@ -299,7 +307,7 @@ pub fn final_scene() -> HittableList {
         }
     }
     let mut objects = HittableList::default();
-    objects.add(Box::new(BVHNode::new(boxes1, TIME0, TIME1)));
+    objects.add(Box::new(BVHNode::new(boxes1, 0.0, 0.0)));
 
     let light = DiffuseLight::new_from_color(&Color::new(7.0, 7.0, 7.0));
     objects.add(Box::new(FlipFace::new(XZRect::new(
@@ -314,7 +322,7 @@ pub fn final_scene() -> HittableList {
         &center2,
         50.,
         0.,
-        1.,
+        CameraSettings::default().shutter_time,
         moving_sphere_material,
     )));
 
@@ -337,10 +345,11 @@ pub fn final_scene() -> HittableList {
         &Color::new(0.2, 0.4, 0.9),
     )));
     let boundary = Sphere::new(&Point3::new(0., 0., 0.), 5000., Dielectric::new(1.5));
-    objects.add(Box::new(ConstantMedium::new_from_color(
+    objects.add(Box::new(ConstantMedium::new_from_color_with_phase(
         boundary,
         0.0001,
         &Color::new(1., 1., 1.),
+        HenyeyGreenstein::new(0.7),
     )));
 
     let earth_text = ImageTexture::new("raytracer/sources/earthmap.jpg");
@@ -371,7 +380,7 @@ pub fn final_scene() -> HittableList {
         )));
     }
 
-    let bvh_ptr = BVHNode::new(boxes2, TIME0, TIME1);
+    let bvh_ptr = BVHNode::new(boxes2, 0.0, 0.0);
     objects.add(Box::new(Translate::new(
         RotateY::new(bvh_ptr, 15.0),
         &Vec3::new(-100., 270., 395.),
@@ -384,10 +393,11 @@ pub fn final_scene() -> HittableList {
 
 pub fn golden_cow_in_cornell_box() -> HittableList {
     let mut objects = HittableList::default();
-    let red = Lambertian::new_from_color(&Color::new(0.65, 0.05, 0.05));
-    let white = Lambertian::new_from_color(&Color::new(0.73, 0.73, 0.73));
-    let green = Lambertian::new_from_color(&Color::new(0.12, 0.45, 0.15));
-    let light = DiffuseLight::new_from_color(&Color::new(15.0, 15.0, 15.0));
+    let library = MaterialLibrary::load(MATERIAL_LIBRARY);
+    let red = library.material("cornell_red");
+    let white = library.material("cornell_white");
+    let green = library.material("cornell_green");
+    let light = library.material("cornell_light");
     objects.add(Box::new(YZRect::new(0., 555., 0., 555., 555., green)));
     objects.add(Box::new(YZRect::new(0., 555., 0., 555., 0., red)));
     //flip light
@@ -406,12 +416,16 @@ pub fn golden_cow_in_cornell_box() -> HittableList {
     )));
     objects.add(Box::new(XYRect::new(0., 555., 0., 555., 555., white)));
 
-    let light = DiffuseLight::new_from_color(&Color::new(0.55, 0.55, 0.55));
+    let light = library.material("sky_fill");
     objects.add(Box::new(XYRect::new(
         -90000., 90000., -90000., 90000., -4000., light,
     )));
 
-    let cow = load_naive("objects/spot_triangulated_good.obj", Metal::gold(), 200.0);
+    let cow = load_naive(
+        "objects/spot_triangulated_good.obj",
+        library.material("gold"),
+        200.0,
+    );
 
     objects.add(Box::new(Translate::new(cow, &Vec3::new(278., 144., 178.))));
 
@@ -420,10 +434,11 @@ pub fn golden_cow_in_cornell_box() -> HittableList {
 
 pub fn car_in_cornell_box() -> HittableList {
     let mut objects = HittableList::default();
-    let red = Lambertian::new_from_color(&Color::new(0.65, 0.05, 0.05));
-    let white = Lambertian::new_from_color(&Color::new(0.73, 0.73, 0.73));
-    let green = Lambertian::new_from_color(&Color::new(0.12, 0.45, 0.15));
-    let light = DiffuseLight::new_from_color(&Color::new(15.0, 15.0, 15.0));
+    let library = MaterialLibrary::load(MATERIAL_LIBRARY);
+    let red = library.material("cornell_red");
+    let white = library.material("cornell_white");
+    let green = library.material("cornell_green");
+    let light = library.material("cornell_light");
     objects.add(Box::new(YZRect::new(0., 555., 0., 555., 555., green)));
     objects.add(Box::new(YZRect::new(0., 555., 0., 555., 0., red)));
     //flip light
@@ -442,12 +457,14 @@ pub fn car_in_cornell_box() -> HittableList {
     )));
     objects.add(Box::new(XYRect::new(0., 555., 0., 555., 555., white)));
 
-    let light = DiffuseLight::new_from_color(&Color::new(0.55, 0.55, 0.55));
+    let light = library.material("sky_fill");
     objects.add(Box::new(XYRect::new(
         -90000., 90000., -90000., 90000., -4000., light,
     )));
 
-    let obj = load_naive("objects/car.obj", Metal::gold(), 120.0);
+    let paint = library.material("gold");
+    // let paint = library.material("car_paint");
+    let obj = load_naive("objects/car.obj", paint, 120.0);
 
     objects.add(Box::new(Translate::new(
         RotateY::new(obj, 180.0),
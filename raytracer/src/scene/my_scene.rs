@@ -1,7 +1,9 @@
+use crate::camera::animation::*;
 use crate::camera::Camera;
 use crate::hittable::aarect::*;
 use crate::hittable::sphere::Sphere;
 use crate::hittable::*;
+use crate::material::conductor::Conductor;
 use crate::material::*;
 use crate::obj_loader::*;
 use crate::utility::vec3::*;
@@ -90,8 +92,8 @@ pub fn final_work() -> (HittableList, Camera) {
         &Vec3::new(-800., 140., -150.),
     )));
 
-    let mat = Metal::silver();
-    let destroyer = load_naive("objects/Destroyer.obj", mat, 30.0);
+    let mat = Conductor::silver();
+    let destroyer = load_naive("objects/Destroyer.obj", mat.clone(), 30.0);
     objects.add(Box::new(Translate::new(
         RotateY::new(RotateX::new(destroyer, -90.0), -15.0),
         &Vec3::new(-1000., 90., 550.),
@@ -116,3 +118,15 @@ pub fn final_work() -> (HittableList, Camera) {
     let lookat = Point3::new(0.0, 70.0, 0.0);
     (world, Camera::for_final(&lookfrom, &lookat))
 }
+
+//fly around the fleet, ending at the view of Camera::for_final
+pub fn final_work_flight() -> CameraPath {
+    let lookat = Point3::new(0.0, 70.0, 0.0);
+    let keyframes = vec![
+        CameraKeyframe::new(0.0, &Point3::new(-300.0, 400.0, 900.0), &lookat, 55.0, 10.0),
+        CameraKeyframe::new(2.0, &Point3::new(300.0, 250.0, 700.0), &lookat, 50.0, 10.0),
+        CameraKeyframe::new(4.0, &Point3::new(700.0, 180.0, 300.0), &lookat, 45.0, 10.0),
+        CameraKeyframe::new(6.0, &Point3::new(600.0, 150.0, 0.0), &lookat, 40.0, 10.0),
+    ];
+    CameraPath::new(keyframes, Interpolation::CatmullRom)
+}
@@ -0,0 +1,87 @@
+use crate::hittable::curve::{Curve, CurveType};
+use crate::hittable::HittableList;
+use crate::material::Material;
+use crate::utility::vec3::*;
+
+const DEFAULT_WIDTH: (f64, f64) = (0.01, 0.002); //at the root and at the tip
+
+// Hair and fur as plain text, one strand per line:
+//
+//   # comment
+//   width 0.02 0.004            root and tip width of the strands below
+//   x y z  x y z  x y z ...     points of a strand from root to tip
+//
+// Each strand passes through its points as a Catmull-Rom spline, made of one cubic Bézier
+// curve between consecutive points. The width shrinks linearly from root to tip.
+pub fn load_strands<M: Material + Clone + 'static>(
+    pathname: &str,
+    curve_type: CurveType,
+    mat: M,
+    scale: f64,
+) -> HittableList {
+    let text = std::fs::read_to_string(pathname).expect("Failed to load strand file.");
+    let mut width = DEFAULT_WIDTH;
+    let mut objects = HittableList::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parse = |s: &str| -> f64 {
+            s.parse().unwrap_or_else(|_| {
+                panic!("Bad number {:?} on line {} of {}", s, number + 1, pathname)
+            })
+        };
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields[0] == "width" {
+            if fields.len() != 3 {
+                panic!(
+                    "Expected root and tip width on line {} of {}",
+                    number + 1,
+                    pathname
+                );
+            }
+            width = (parse(fields[1]) * scale, parse(fields[2]) * scale);
+            continue;
+        }
+        if fields.len() % 3 != 0 || fields.len() < 6 {
+            panic!(
+                "Expected at least two points on line {} of {}",
+                number + 1,
+                pathname
+            );
+        }
+        let points: Vec<Point3> = fields
+            .chunks(3)
+            .map(|p| Point3::new(parse(p[0]), parse(p[1]), parse(p[2])) * scale)
+            .collect();
+        for curve in strand_curves(&points, width, curve_type, mat.clone()) {
+            objects.add(Box::new(curve));
+        }
+    }
+    if objects.size() >= 6 {
+        HittableList::bvh(objects)
+    } else {
+        objects
+    }
+}
+
+//Bézier curves of the Catmull-Rom spline through the points, the ends repeat their point
+pub fn strand_curves<M: Material + Clone>(
+    points: &[Point3],
+    width: (f64, f64),
+    curve_type: CurveType,
+    mat: M,
+) -> Vec<Curve<M>> {
+    let n = points.len();
+    let width_at = |i: usize| width.0 + (width.1 - width.0) * i as f64 / (n - 1) as f64;
+    (0..n - 1)
+        .map(|i| {
+            let p0 = points[i.saturating_sub(1)];
+            let (p1, p2) = (points[i], points[i + 1]);
+            let p3 = points[(i + 2).min(n - 1)];
+            let cp = [p1, p1 + (p2 - p0) / 6.0, p2 - (p3 - p1) / 6.0, p2];
+            Curve::new(&cp, (width_at(i), width_at(i + 1)), curve_type, mat.clone())
+        })
+        .collect()
+}
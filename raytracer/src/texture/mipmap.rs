@@ -0,0 +1,248 @@
+use crate::utility::vec3::*;
+
+const MAX_ANISOTROPY: f64 = 8.0; //longest ratio of the axes of an EWA footprint
+const EWA_ALPHA: f64 = 2.0; //falloff of the gaussian EWA weights
+
+//what a lookup outside [0, 1] finds
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum WrapMode {
+    Repeat,
+    Mirror, //repeats, every other copy flipped
+    Clamp,  //the edge texels stretch outwards
+    Border(Color),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,  //of the full resolution image, whatever the footprint
+    Trilinear, //bilinear in the two levels around the width of the footprint
+    EWA,       //elliptical weighted average over the footprint (Heckbert 1989), for grazing views
+}
+
+struct Level {
+    width: usize,
+    height: usize,
+    texels: Vec<[f32; 3]>, //rows from the top
+}
+
+// Image pyramid, each level half the size of the one before down to a single texel.
+// Lookups take s to the right and t down the image, both in [0, 1] over the whole image.
+pub struct MipMap {
+    levels: Vec<Level>,
+}
+
+impl MipMap {
+    pub fn new(width: usize, height: usize, texels: Vec<[f32; 3]>) -> Self {
+        let mut levels = vec![Level {
+            width,
+            height,
+            texels,
+        }];
+        if width == 0 || height == 0 {
+            return Self { levels: Vec::new() };
+        }
+        while levels.last().map_or(false, |l| l.width > 1 || l.height > 1) {
+            let last = levels.last().unwrap();
+            let (width, height) = ((last.width + 1) / 2, (last.height + 1) / 2);
+            let mut texels = Vec::with_capacity(width * height);
+            for j in 0..height {
+                for i in 0..width {
+                    //box filter, the last row and column repeat on odd sizes
+                    let mut sum = [0.0; 3];
+                    for (di, dj) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                        let x = (2 * i + di).min(last.width - 1);
+                        let y = (2 * j + dj).min(last.height - 1);
+                        let texel = last.texels[y * last.width + x];
+                        for c in 0..3 {
+                            sum[c] += 0.25 * texel[c];
+                        }
+                    }
+                    texels.push(sum);
+                }
+            }
+            levels.push(Level {
+                width,
+                height,
+                texels,
+            });
+        }
+        Self { levels }
+    }
+
+    pub fn empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+
+    pub fn levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn width(&self) -> usize {
+        self.levels.first().map_or(0, |l| l.width)
+    }
+
+    pub fn height(&self) -> usize {
+        self.levels.first().map_or(0, |l| l.height)
+    }
+
+    pub fn texel(&self, level: usize, i: i64, j: i64, wrap: WrapMode) -> Color {
+        let level = &self.levels[level];
+        let wrapped = |x: i64, size: usize| {
+            let size = size as i64;
+            match wrap {
+                WrapMode::Repeat => Some(x.rem_euclid(size)),
+                WrapMode::Mirror => {
+                    let x = x.rem_euclid(2 * size);
+                    Some(if x >= size { 2 * size - 1 - x } else { x })
+                }
+                WrapMode::Clamp => Some(x.max(0).min(size - 1)),
+                WrapMode::Border(_) => {
+                    if x < 0 || x >= size {
+                        None
+                    } else {
+                        Some(x)
+                    }
+                }
+            }
+        };
+        match (wrapped(i, level.width), wrapped(j, level.height)) {
+            (Some(i), Some(j)) => {
+                let texel = level.texels[j as usize * level.width + i as usize];
+                Color::new(texel[0] as f64, texel[1] as f64, texel[2] as f64)
+            }
+            _ => match wrap {
+                WrapMode::Border(color) => color,
+                _ => Color::default(),
+            },
+        }
+    }
+
+    pub fn nearest(&self, s: f64, t: f64, wrap: WrapMode) -> Color {
+        let level = &self.levels[0];
+        let i = (s * level.width as f64).floor() as i64;
+        let j = (t * level.height as f64).floor() as i64;
+        self.texel(0, i, j, wrap)
+    }
+
+    pub fn bilinear(&self, level: usize, s: f64, t: f64, wrap: WrapMode) -> Color {
+        let level_index = level.min(self.levels.len() - 1);
+        let level = &self.levels[level_index];
+        //texel centres sit at half integers
+        let x = s * level.width as f64 - 0.5;
+        let y = t * level.height as f64 - 0.5;
+        let (i, j) = (x.floor(), y.floor());
+        let (fx, fy) = (x - i, y - j);
+        let (i, j) = (i as i64, j as i64);
+        (1.0 - fx) * (1.0 - fy) * self.texel(level_index, i, j, wrap)
+            + fx * (1.0 - fy) * self.texel(level_index, i + 1, j, wrap)
+            + (1.0 - fx) * fy * self.texel(level_index, i, j + 1, wrap)
+            + fx * fy * self.texel(level_index, i + 1, j + 1, wrap)
+    }
+
+    //width is the diameter of the footprint in [0, 1] image units
+    pub fn trilinear(&self, s: f64, t: f64, width: f64, wrap: WrapMode) -> Color {
+        let resolution = self.width().max(self.height()) as f64;
+        let level = (width * resolution).max(1e-8).log2();
+        self.between_levels(level, |l| self.bilinear(l, s, t, wrap))
+    }
+
+    //axes are the half axes of the footprint ellipse in [0, 1] image units
+    pub fn ewa(&self, s: f64, t: f64, axes: [(f64, f64); 2], wrap: WrapMode) -> Color {
+        let length = |a: (f64, f64)| (a.0 * a.0 + a.1 * a.1).sqrt();
+        let (major, mut minor) = if length(axes[0]) < length(axes[1]) {
+            (axes[1], axes[0])
+        } else {
+            (axes[0], axes[1])
+        };
+        let (major_length, mut minor_length) = (length(major), length(minor));
+        if major_length == 0.0 {
+            return self.bilinear(0, s, t, wrap);
+        }
+        //a too long ellipse would cover too many texels, widen it and blur a little instead
+        if minor_length * MAX_ANISOTROPY < major_length {
+            let target = major_length / MAX_ANISOTROPY;
+            minor = if minor_length > 0.0 {
+                let f = target / minor_length;
+                (minor.0 * f, minor.1 * f)
+            } else {
+                let f = target / major_length;
+                (-major.1 * f, major.0 * f)
+            };
+            minor_length = target;
+        }
+        let resolution = self.width().max(self.height()) as f64;
+        let level = (minor_length * resolution).max(1e-8).log2();
+        self.between_levels(level, |l| self.ewa_level(l, s, t, major, minor, wrap))
+    }
+
+    //blend of the lookups at the two levels around a fractional level
+    fn between_levels<F: Fn(usize) -> Color>(&self, level: f64, lookup: F) -> Color {
+        let last = self.levels.len() - 1;
+        if level <= 0.0 {
+            return lookup(0);
+        }
+        if level >= last as f64 {
+            return lookup(last);
+        }
+        let below = level.floor() as usize;
+        let f = level - below as f64;
+        (1.0 - f) * lookup(below) + f * lookup(below + 1)
+    }
+
+    fn ewa_level(
+        &self,
+        level_index: usize,
+        s: f64,
+        t: f64,
+        axis0: (f64, f64),
+        axis1: (f64, f64),
+        wrap: WrapMode,
+    ) -> Color {
+        let level = &self.levels[level_index];
+        let (w, h) = (level.width as f64, level.height as f64);
+        //into texel units of this level
+        let (x, y) = (s * w - 0.5, t * h - 0.5);
+        let a0 = (axis0.0 * w, axis0.1 * h);
+        let a1 = (axis1.0 * w, axis1.1 * h);
+
+        //implicit ellipse A s^2 + B s t + C t^2 < 1, widened by a texel so it never misses them all
+        let mut a = a0.1 * a0.1 + a1.1 * a1.1 + 1.0;
+        let mut b = -2.0 * (a0.0 * a0.1 + a1.0 * a1.1);
+        let mut c = a0.0 * a0.0 + a1.0 * a1.0 + 1.0;
+        let inv_f = 1.0 / (a * c - b * b * 0.25);
+        a *= inv_f;
+        b *= inv_f;
+        c *= inv_f;
+
+        //bounding box of the ellipse
+        let det = -b * b + 4.0 * a * c;
+        let inv_det = 1.0 / det;
+        let u_sqrt = (det * c).sqrt();
+        let v_sqrt = (a * det).sqrt();
+        let s0 = (x - 2.0 * inv_det * u_sqrt).ceil() as i64;
+        let s1 = (x + 2.0 * inv_det * u_sqrt).floor() as i64;
+        let t0 = (y - 2.0 * inv_det * v_sqrt).ceil() as i64;
+        let t1 = (y + 2.0 * inv_det * v_sqrt).floor() as i64;
+
+        let mut sum = Color::default();
+        let mut weight_sum = 0.0;
+        for it in t0..=t1 {
+            let tt = it as f64 - y;
+            for is in s0..=s1 {
+                let ss = is as f64 - x;
+                let r2 = a * ss * ss + b * ss * tt + c * tt * tt;
+                if r2 < 1.0 {
+                    let weight = (-EWA_ALPHA * r2).exp() - (-EWA_ALPHA).exp();
+                    sum += weight * self.texel(level_index, is, it, wrap);
+                    weight_sum += weight;
+                }
+            }
+        }
+        if weight_sum > 0.0 {
+            sum / weight_sum
+        } else {
+            self.bilinear(level_index, s, t, wrap)
+        }
+    }
+}
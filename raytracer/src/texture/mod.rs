@@ -1,13 +1,87 @@
+pub mod mipmap;
 pub mod perlin;
+pub mod procedural;
 
-use crate::texture::perlin::Perlin;
-use crate::utility::clamp;
+use crate::hittable::HitRecord;
+use crate::pdf::onb::ONB;
+use crate::texture::mipmap::{Filter, MipMap, WrapMode};
+use crate::texture::perlin::{Noise, Perlin};
+use crate::utility::ray::Ray;
 use crate::utility::vec3::*;
 use image::GenericImageView;
 use std::sync::Arc;
 
+const MIN_FOOTPRINT_COS: f64 = 1e-3; //limits the stretch of footprints at grazing angles
+
 pub trait Texture: Send + Sync {
     fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+
+    //for textures driving a single material parameter
+    fn scalar(&self, u: f64, v: f64, p: &Point3) -> f64 {
+        let c = self.value(u, v, p);
+        (c.x() + c.y() + c.z()) / 3.0
+    }
+
+    //average over a footprint, textures with no detail finer than it take the centre
+    fn filtered(&self, u: f64, v: f64, p: &Point3, _footprint: &Footprint) -> Color {
+        self.value(u, v, p)
+    }
+
+    //value at a hit, filtered over the footprint of the ray that found it
+    fn value_at(&self, rec: &HitRecord) -> Color {
+        self.filtered(rec.u, rec.v, &rec.p, &rec.footprint)
+    }
+
+    fn scalar_at(&self, rec: &HitRecord) -> f64 {
+        let c = self.value_at(rec);
+        (c.x() + c.y() + c.z()) / 3.0
+    }
+}
+
+// The region of uv space a ray cone covers where it hits a surface, as the two half axes of an
+// ellipse. Zero axes stand for a thin ray.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Footprint {
+    pub axes: [(f64, f64); 2],
+}
+
+impl Footprint {
+    // The circular cross-section of the cone is projected onto the surface, where it stretches
+    // along the ray by the grazing angle, and then into uv through dpdu and dpdv.
+    pub fn new(r: &Ray, rec: &HitRecord) -> Self {
+        let length = r.direction().length();
+        let radius = 0.5 * r.cone.width_at(rec.t * length);
+        let (dpdu, dpdv) = (rec.dpdu, rec.dpdv);
+        let (uu, uv, vv) = (dot(&dpdu, &dpdu), dot(&dpdu, &dpdv), dot(&dpdv, &dpdv));
+        let det = uu * vv - uv * uv;
+        if radius <= 0.0 || det <= 1e-12 * uu * vv || length == 0.0 {
+            return Self::default();
+        }
+        let n = rec.geometric_normal;
+        let d = r.direction() / length;
+        let cos = dot(&d, &n).abs().max(MIN_FOOTPRINT_COS);
+        let along = d - dot(&d, &n) * n;
+        let along = if along.length_squared() > 1e-12 {
+            along.unit()
+        } else {
+            ONB::build_from_w(&n).u()
+        };
+        let across = cross(&n, &along);
+        //least squares solution of a = du dpdu + dv dpdv
+        let to_uv = |a: Vec3| {
+            let (au, av) = (dot(&a, &dpdu), dot(&a, &dpdv));
+            ((vv * au - uv * av) / det, (uu * av - uv * au) / det)
+        };
+        Self {
+            axes: [to_uv(radius * across), to_uv(radius / cos * along)],
+        }
+    }
+
+    //diameter of the circle around the ellipse
+    pub fn width(&self) -> f64 {
+        let length = |a: (f64, f64)| (a.0 * a.0 + a.1 * a.1).sqrt();
+        2.0 * length(self.axes[0]).max(length(self.axes[1]))
+    }
 }
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -27,6 +101,12 @@ impl SolidColor {
             color_value: Color::new(red, green, blue),
         }
     }
+
+    pub fn new_from_value(value: f64) -> Self {
+        Self {
+            color_value: Color::same(value),
+        }
+    }
 }
 
 impl Texture for SolidColor {
@@ -35,6 +115,47 @@ impl Texture for SolidColor {
     }
 }
 
+// Shared textures of any kind, as materials read from files hold them.
+impl Texture for Arc<dyn Texture> {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        (**self).value(u, v, p)
+    }
+
+    fn scalar(&self, u: f64, v: f64, p: &Point3) -> f64 {
+        (**self).scalar(u, v, p)
+    }
+
+    fn filtered(&self, u: f64, v: f64, p: &Point3, footprint: &Footprint) -> Color {
+        (**self).filtered(u, v, p, footprint)
+    }
+}
+
+//a texture multiplied by a colour, like an MTL map_Kd by its Kd
+#[derive(Clone, Default)]
+pub struct TintedTexture<T: Texture> {
+    pub texture: T,
+    pub tint: Color,
+}
+
+impl<T: Texture> TintedTexture<T> {
+    pub fn new(texture: T, tint: &Color) -> Self {
+        Self {
+            texture,
+            tint: *tint,
+        }
+    }
+}
+
+impl<T: Texture> Texture for TintedTexture<T> {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        self.texture.value(u, v, p) * self.tint
+    }
+
+    fn filtered(&self, u: f64, v: f64, p: &Point3, footprint: &Footprint) -> Color {
+        self.texture.filtered(u, v, p, footprint) * self.tint
+    }
+}
+
 #[derive(Clone, Default)]
 pub struct CheckerTexture<T1: Texture, T2: Texture> {
     pub even: T1,
@@ -65,6 +186,14 @@ impl<T1: Texture, T2: Texture> Texture for CheckerTexture<T1, T2> {
             self.even.value(u, v, p)
         }
     }
+    fn filtered(&self, u: f64, v: f64, p: &Point3, footprint: &Footprint) -> Color {
+        let sines = (10.0 * p.x()).sin() * (10.0 * p.y()).sin() * (10.0 * p.z()).sin();
+        if sines < 0.0 {
+            self.odd.filtered(u, v, p, footprint)
+        } else {
+            self.even.filtered(u, v, p, footprint)
+        }
+    }
 }
 
 #[derive(Clone, Default)]
@@ -77,7 +206,7 @@ impl Texture for NoiseTexture {
     fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
         Color::new(1., 1., 1.)
             * 0.5
-            * (1.0 + (self.scale * p.z() + 10.0 * self.noise.turbulence(p)).sin())
+            * (1.0 + (self.scale * p.z() + 10.0 * self.noise.turbulence(p, 7)).sin())
     }
 }
 
@@ -90,62 +219,120 @@ impl NoiseTexture {
     }
 }
 
+// Image looked up with u to the right and v up, filtered over the footprint of the ray.
 #[derive(Clone)]
 pub struct ImageTexture {
-    data: Arc<Vec<u8>>,
-    width: u32,
-    height: u32,
-    bytes_per_scanline: u32,
+    mipmap: Arc<MipMap>,
+    alpha: Arc<Vec<u8>>, //one byte per pixel, empty for opaque images
+    pub wrap: WrapMode,
+    pub filter: Filter,
 }
 
 impl ImageTexture {
     pub const BYTES_PER_PIXEL: u32 = 3;
 
     pub fn new(pathname: &str) -> Self {
-        let img = image::open(pathname).expect("Fail to load image file.");
+        Self::load(pathname).expect("Fail to load image file.")
+    }
+
+    pub fn load(pathname: &str) -> Result<Self, image::ImageError> {
+        let img = image::open(pathname)?;
         let data = img.to_rgb8().into_vec();
+        let alpha = if img.color().has_alpha() {
+            img.to_rgba8()
+                .into_vec()
+                .into_iter()
+                .skip(3)
+                .step_by(4)
+                .collect()
+        } else {
+            Vec::new()
+        };
         let (width, height) = img.dimensions();
+        Ok(Self::from_bytes(
+            &data,
+            width as usize,
+            height as usize,
+            alpha,
+        ))
+    }
+
+    fn from_bytes(data: &[u8], width: usize, height: usize, alpha: Vec<u8>) -> Self {
+        let color_scale = 1.0 / 255.0;
+        let texels = data
+            .chunks(ImageTexture::BYTES_PER_PIXEL as usize)
+            .map(|c| {
+                [
+                    c[0] as f32 * color_scale,
+                    c[1] as f32 * color_scale,
+                    c[2] as f32 * color_scale,
+                ]
+            })
+            .collect();
         Self {
-            data: Arc::new(data),
-            width,
-            height,
-            bytes_per_scanline: ImageTexture::BYTES_PER_PIXEL * width,
+            mipmap: Arc::new(MipMap::new(width, height, texels)),
+            alpha: Arc::new(alpha),
+            wrap: WrapMode::Clamp,
+            filter: Filter::Trilinear,
         }
     }
 
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
     pub fn empty(&self) -> bool {
-        self.data.is_empty()
+        self.mipmap.empty()
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        !self.alpha.is_empty()
+    }
+
+    //the alpha channel as a grey texture, for cutouts
+    pub fn alpha_channel(&self) -> Self {
+        let data: Vec<u8> = self.alpha.iter().flat_map(|&a| [a, a, a]).collect();
+        let (width, height) = (self.mipmap.width(), self.mipmap.height());
+        Self {
+            wrap: self.wrap,
+            filter: self.filter,
+            ..Self::from_bytes(&data, width, height, Vec::new())
+        }
     }
 }
 
 impl Default for ImageTexture {
     fn default() -> Self {
-        Self {
-            data: Arc::new(Vec::new()),
-            width: 0,
-            height: 0,
-            bytes_per_scanline: 0,
-        }
+        Self::from_bytes(&[], 0, 0, Vec::new())
     }
 }
 
 impl Texture for ImageTexture {
-    fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
-        // Clamp input texture coordinates to [0,1] x [1,0]
-        let u = clamp(u, 0.0, 1.0);
-        let v = 1.0 - clamp(v, 0.0, 1.0);
-        let mut i = (u * self.width as f64) as u32;
-        let mut j = (v * self.height as f64) as u32;
-        // Clamp integer mapping, since actual coordinates should be less than 1.0
-        i = i.min(self.width - 1);
-        j = j.min(self.height - 1);
-        let color_scale = 1.0 / 255.0;
-        let index = (j * self.bytes_per_scanline + i * ImageTexture::BYTES_PER_PIXEL) as usize;
-        color_scale
-            * Color::new(
-                self.data[index] as f64,
-                self.data[index + 1] as f64,
-                self.data[index + 2] as f64,
-            )
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        self.filtered(u, v, p, &Footprint::default())
+    }
+
+    fn filtered(&self, u: f64, v: f64, _p: &Point3, footprint: &Footprint) -> Color {
+        if self.empty() {
+            return Color::default();
+        }
+        //images run downwards
+        let (s, t) = (u, 1.0 - v);
+        match self.filter {
+            Filter::Nearest => self.mipmap.nearest(s, t, self.wrap),
+            Filter::Bilinear => self.mipmap.bilinear(0, s, t, self.wrap),
+            Filter::Trilinear => self.mipmap.trilinear(s, t, footprint.width(), self.wrap),
+            Filter::EWA => {
+                let [a0, a1] = footprint.axes;
+                let axes = [(a0.0, -a0.1), (a1.0, -a1.1)];
+                self.mipmap.ewa(s, t, axes, self.wrap)
+            }
+        }
     }
 }
@@ -1,5 +1,65 @@
-use crate::utility::random_int_range;
 use crate::utility::vec3::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// Smooth pseudo random values over space, in about [-1, 1], and the fractal sums of octaves of
+// them that procedural textures are built from. Octaves rise in frequency by lacunarity and
+// fall in amplitude by gain.
+pub trait Noise: Send + Sync {
+    fn noise(&self, p: &Point3) -> f64;
+
+    //fractional Brownian motion, in about [-1, 1]
+    fn fbm(&self, p: &Point3, octaves: usize, lacunarity: f64, gain: f64) -> f64 {
+        let (mut sum, mut norm) = (0.0, 0.0);
+        let (mut amplitude, mut frequency) = (1.0, 1.0);
+        for _i in 0..octaves {
+            sum += amplitude * self.noise(&(*p * frequency));
+            norm += amplitude;
+            amplitude *= gain;
+            frequency *= lacunarity;
+        }
+        if norm > 0.0 {
+            sum / norm
+        } else {
+            0.0
+        }
+    }
+
+    //octaves halving in size and weight, the magnitude of their sum
+    fn turbulence(&self, p: &Point3, depth: usize) -> f64 {
+        let mut accum = 0.0;
+        let mut temp_p = *p;
+        let mut weight = 1.0;
+        for _i in 0..depth {
+            accum += weight * self.noise(&temp_p);
+            weight *= 0.5;
+            temp_p *= 2.0;
+        }
+        accum.abs()
+    }
+
+    // Ridged multifractal (Musgrave): sharp crests where the noise crosses zero, each octave
+    // weighted by the one before so the detail gathers on the crests. In [0, 1], offset is
+    // usually 1.
+    fn ridged(&self, p: &Point3, octaves: usize, lacunarity: f64, gain: f64, offset: f64) -> f64 {
+        let (mut sum, mut norm) = (0.0, 0.0);
+        let (mut amplitude, mut frequency, mut weight) = (1.0, 1.0, 1.0);
+        for _i in 0..octaves {
+            let signal = offset - self.noise(&(*p * frequency)).abs();
+            let signal = signal * signal * weight;
+            weight = (2.0 * signal).max(0.0).min(1.0);
+            sum += amplitude * signal;
+            norm += amplitude * offset * offset;
+            amplitude *= gain;
+            frequency *= lacunarity;
+        }
+        if norm > 0.0 {
+            (sum / norm).max(0.0).min(1.0)
+        } else {
+            0.0
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct Perlin {
@@ -13,19 +73,72 @@ impl Perlin {
     const POINT_COUNT: usize = 256;
 
     pub fn new() -> Perlin {
+        Perlin::from_rng(&mut rand::thread_rng())
+    }
+
+    //the same seed always gives the same noise
+    pub fn with_seed(seed: u64) -> Perlin {
+        Perlin::from_rng(&mut StdRng::seed_from_u64(seed))
+    }
+
+    fn from_rng<R: Rng>(rng: &mut R) -> Perlin {
         let mut ran_vec = Vec::new();
         for _i in 0..Perlin::POINT_COUNT {
-            ran_vec.push(Vec3::random_range(-1.0, 1.0).unit());
+            ran_vec.push(
+                Vec3::new(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                )
+                .unit(),
+            );
         }
         Perlin {
             ran_vec,
-            perm_x: Perlin::perlin_generate_perm(),
-            perm_y: Perlin::perlin_generate_perm(),
-            perm_z: Perlin::perlin_generate_perm(),
+            perm_x: Perlin::perlin_generate_perm(rng),
+            perm_y: Perlin::perlin_generate_perm(rng),
+            perm_z: Perlin::perlin_generate_perm(rng),
+        }
+    }
+
+    fn perlin_generate_perm<R: Rng>(rng: &mut R) -> Vec<i32> {
+        let mut p = vec![0; Perlin::POINT_COUNT];
+        for (i, it) in p.iter_mut().enumerate().take(Perlin::POINT_COUNT) {
+            *it = i as i32;
+        }
+        Perlin::permute(&mut p, Perlin::POINT_COUNT, rng);
+        p
+    }
+
+    fn permute<R: Rng>(p: &mut [i32], n: usize, rng: &mut R) {
+        for i in (1..n).rev() {
+            let target = rng.gen_range(0..=i);
+            p.swap(i, target);
         }
     }
 
-    pub fn noise(&self, p: &Point3) -> f64 {
+    fn trilinear_interp(c: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
+        let uu = u * u * (3.0 - 2.0 * u);
+        let vv = v * v * (3.0 - 2.0 * v);
+        let ww = w * w * (3.0 - 2.0 * w);
+        let mut accum = 0.0;
+        for (i, ci) in c.iter().enumerate() {
+            for (j, cj) in ci.iter().enumerate() {
+                for (k, ck) in cj.iter().enumerate() {
+                    let weight_v = Vec3::new(u - i as f64, v - j as f64, w - k as f64);
+                    accum += ((i as f64) * uu + ((1 - i) as f64) * (1.0 - uu))
+                        * ((j as f64) * vv + ((1 - j) as f64) * (1.0 - vv))
+                        * ((k as f64) * ww + ((1 - k) as f64) * (1.0 - ww))
+                        * dot(ck, &weight_v);
+                }
+            }
+        }
+        accum
+    }
+}
+
+impl Noise for Perlin {
+    fn noise(&self, p: &Point3) -> f64 {
         let mut u = p.x() - p.x().floor();
         let mut v = p.y() - p.y().floor();
         let mut w = p.z() - p.z().floor();
@@ -51,58 +164,197 @@ impl Perlin {
 
         Perlin::trilinear_interp(&c, u, v, w)
     }
+}
 
-    pub fn turbulence(&self, p: &Point3) -> f64 {
-        let depth = 7;
-        let mut accum = 0.0;
-        let mut temp_p = *p;
-        let mut weight = 1.0;
-        for _i in 0..depth {
-            accum += weight * self.noise(&temp_p);
-            weight *= 0.5;
-            temp_p *= 2.0;
+impl Default for Perlin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//doubled permutation of 0..256, so that perm[perm[i] + j] needs no wrapping
+fn permutation_table<R: Rng>(rng: &mut R) -> Vec<usize> {
+    let mut perm: Vec<usize> = (0..256).collect();
+    for i in (1..256).rev() {
+        perm.swap(i, rng.gen_range(0..=i));
+    }
+    perm.extend_from_within(..);
+    perm
+}
+
+// Simplex noise (Perlin 2001, after Gustavson's "Simplex noise demystified"): gradients at the
+// corners of tetrahedra instead of cubes, cheaper and without the axis aligned look of Perlin.
+#[derive(Clone)]
+pub struct Simplex {
+    perm: Vec<usize>,
+}
+
+impl Simplex {
+    const GRADIENTS: [[f64; 3]; 12] = [
+        [1.0, 1.0, 0.0],
+        [-1.0, 1.0, 0.0],
+        [1.0, -1.0, 0.0],
+        [-1.0, -1.0, 0.0],
+        [1.0, 0.0, 1.0],
+        [-1.0, 0.0, 1.0],
+        [1.0, 0.0, -1.0],
+        [-1.0, 0.0, -1.0],
+        [0.0, 1.0, 1.0],
+        [0.0, -1.0, 1.0],
+        [0.0, 1.0, -1.0],
+        [0.0, -1.0, -1.0],
+    ];
+
+    pub fn new() -> Self {
+        Self {
+            perm: permutation_table(&mut rand::thread_rng()),
         }
-        accum.abs()
     }
 
-    fn perlin_generate_perm() -> Vec<i32> {
-        let mut p = vec![0; Perlin::POINT_COUNT];
-        for (i, it) in p.iter_mut().enumerate().take(Perlin::POINT_COUNT) {
-            *it = i as i32;
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            perm: permutation_table(&mut StdRng::seed_from_u64(seed)),
         }
-        Perlin::permute(&mut p, Perlin::POINT_COUNT);
-        p
     }
+}
 
-    fn permute(p: &mut [i32], n: usize) {
-        for i in (1..n).rev() {
-            let target = random_int_range(0, i as i32);
-            p.swap(i, target as usize);
+impl Default for Simplex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Noise for Simplex {
+    fn noise(&self, p: &Point3) -> f64 {
+        const F3: f64 = 1.0 / 3.0; //skews space into cubes of tetrahedra
+        const G3: f64 = 1.0 / 6.0; //and back
+
+        let s = (p.x() + p.y() + p.z()) * F3;
+        let (i, j, k) = (
+            (p.x() + s).floor(),
+            (p.y() + s).floor(),
+            (p.z() + s).floor(),
+        );
+        let t = (i + j + k) * G3;
+        let x0 = [p.x() - (i - t), p.y() - (j - t), p.z() - (k - t)];
+
+        //which of the six tetrahedra of the cube, by the order of the coordinates
+        let (step1, step2) = if x0[0] >= x0[1] {
+            if x0[1] >= x0[2] {
+                ([1, 0, 0], [1, 1, 0])
+            } else if x0[0] >= x0[2] {
+                ([1, 0, 0], [1, 0, 1])
+            } else {
+                ([0, 0, 1], [1, 0, 1])
+            }
+        } else if x0[1] < x0[2] {
+            ([0, 0, 1], [0, 1, 1])
+        } else if x0[0] < x0[2] {
+            ([0, 1, 0], [0, 1, 1])
+        } else {
+            ([0, 1, 0], [1, 1, 0])
+        };
+
+        let (ii, jj, kk) = (
+            (i as i64 & 255) as usize,
+            (j as i64 & 255) as usize,
+            (k as i64 & 255) as usize,
+        );
+        let corners = [[0, 0, 0], step1, step2, [1, 1, 1]];
+        let mut sum = 0.0;
+        for (n, corner) in corners.iter().enumerate() {
+            let offset = n as f64 * G3;
+            let x = [
+                x0[0] - corner[0] as f64 + offset,
+                x0[1] - corner[1] as f64 + offset,
+                x0[2] - corner[2] as f64 + offset,
+            ];
+            let falloff = 0.6 - x[0] * x[0] - x[1] * x[1] - x[2] * x[2];
+            if falloff > 0.0 {
+                let hash = self.perm
+                    [ii + corner[0] + self.perm[jj + corner[1] + self.perm[kk + corner[2]]]];
+                let g = Simplex::GRADIENTS[hash % 12];
+                let falloff = falloff * falloff;
+                sum += falloff * falloff * (g[0] * x[0] + g[1] * x[1] + g[2] * x[2]);
+            }
         }
+        32.0 * sum
     }
+}
 
-    fn trilinear_interp(c: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
-        let uu = u * u * (3.0 - 2.0 * u);
-        let vv = v * v * (3.0 - 2.0 * v);
-        let ww = w * w * (3.0 - 2.0 * w);
-        let mut accum = 0.0;
-        for (i, ci) in c.iter().enumerate() {
-            for (j, cj) in ci.iter().enumerate() {
-                for (k, ck) in cj.iter().enumerate() {
-                    let weight_v = Vec3::new(u - i as f64, v - j as f64, w - k as f64);
-                    accum += ((i as f64) * uu + ((1 - i) as f64) * (1.0 - uu))
-                        * ((j as f64) * vv + ((1 - j) as f64) * (1.0 - vv))
-                        * ((k as f64) * ww + ((1 - k) as f64) * (1.0 - ww))
-                        * dot(ck, &weight_v);
+// Worley (cellular) noise: one feature point jittered in every unit cell, and the distances to
+// the nearest two of them. Gives cells, cracks and stones.
+#[derive(Clone)]
+pub struct Worley {
+    perm: Vec<usize>,
+    points: Vec<Vec3>, //offset of the feature point in its cell, by hash
+    values: Vec<f64>,  //a random value of each cell in [0, 1), by hash
+    pub jitter: f64,   //0 puts the points on a grid, 1 anywhere in their cells
+}
+
+impl Worley {
+    pub fn new() -> Self {
+        Self::from_rng(&mut rand::thread_rng())
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Self::from_rng(&mut StdRng::seed_from_u64(seed))
+    }
+
+    fn from_rng<R: Rng>(rng: &mut R) -> Self {
+        let points = (0..256)
+            .map(|_| Vec3::new(rng.gen(), rng.gen(), rng.gen()))
+            .collect();
+        let values = (0..256).map(|_| rng.gen()).collect();
+        Self {
+            perm: permutation_table(rng),
+            points,
+            values,
+            jitter: 1.0,
+        }
+    }
+
+    //distances to the nearest and second nearest points, and the value of the nearest cell
+    pub fn cells(&self, p: &Point3) -> (f64, f64, f64) {
+        let (i, j, k) = (
+            p.x().floor() as i64,
+            p.y().floor() as i64,
+            p.z().floor() as i64,
+        );
+        let (mut f1, mut f2, mut value) = (f64::INFINITY, f64::INFINITY, 0.0);
+        for di in -1..=1 {
+            for dj in -1..=1 {
+                for dk in -1..=1 {
+                    let (ci, cj, ck) = (i + di, j + dj, k + dk);
+                    let hash = self.perm[(ci & 255) as usize
+                        + self.perm[(cj & 255) as usize + self.perm[(ck & 255) as usize]]];
+                    let jittered =
+                        Vec3::same(0.5) + self.jitter * (self.points[hash] - Vec3::same(0.5));
+                    let point = Vec3::new(ci as f64, cj as f64, ck as f64) + jittered;
+                    let distance = (point - *p).length();
+                    if distance < f1 {
+                        f2 = f1;
+                        f1 = distance;
+                        value = self.values[hash];
+                    } else if distance < f2 {
+                        f2 = distance;
+                    }
                 }
             }
         }
-        accum
+        (f1, f2, value)
     }
 }
 
-impl Default for Perlin {
+impl Default for Worley {
     fn default() -> Self {
         Self::new()
     }
 }
+
+impl Noise for Worley {
+    //distance to the nearest point, from -1 on it to 1 a cell away
+    fn noise(&self, p: &Point3) -> f64 {
+        (2.0 * self.cells(p).0 - 1.0).min(1.0)
+    }
+}
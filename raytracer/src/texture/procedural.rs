@@ -0,0 +1,275 @@
+use crate::texture::perlin::{Noise, Perlin, Worley};
+use crate::texture::{Footprint, Texture};
+use crate::utility::vec3::*;
+
+// Colours at positions in [0, 1], blended linearly between the stops and held past the ends.
+#[derive(Debug, Clone, Default)]
+pub struct Ramp {
+    pub stops: Vec<(f64, Color)>,
+}
+
+impl Ramp {
+    pub fn new(mut stops: Vec<(f64, Color)>) -> Self {
+        stops.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        Self { stops }
+    }
+
+    //early to late wood of a ring
+    pub fn wood() -> Self {
+        Self::new(vec![
+            (0.0, Color::new(0.79, 0.58, 0.36)),
+            (0.6, Color::new(0.70, 0.48, 0.28)),
+            (0.9, Color::new(0.42, 0.25, 0.12)),
+            (1.0, Color::new(0.79, 0.58, 0.36)),
+        ])
+    }
+
+    //white stone to grey veins
+    pub fn marble() -> Self {
+        Self::new(vec![
+            (0.0, Color::new(0.93, 0.92, 0.90)),
+            (0.7, Color::new(0.85, 0.84, 0.83)),
+            (0.9, Color::new(0.45, 0.45, 0.48)),
+            (1.0, Color::new(0.20, 0.20, 0.22)),
+        ])
+    }
+
+    //dark mica, grey quartz and pink feldspar
+    pub fn granite() -> Self {
+        Self::new(vec![
+            (0.0, Color::new(0.08, 0.08, 0.08)),
+            (0.3, Color::new(0.35, 0.34, 0.33)),
+            (0.6, Color::new(0.62, 0.60, 0.58)),
+            (0.8, Color::new(0.72, 0.55, 0.50)),
+            (1.0, Color::new(0.85, 0.80, 0.78)),
+        ])
+    }
+
+    pub fn value(&self, x: f64) -> Color {
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Color::default(),
+        };
+        if x <= first.0 {
+            return first.1;
+        }
+        if x >= last.0 {
+            return last.1;
+        }
+        //only NaN is between the ends with no stop above it
+        let upper = match self.stops.iter().position(|s| s.0 > x) {
+            Some(upper) if upper > 0 => upper,
+            _ => return first.1,
+        };
+        let (a, b) = (self.stops[upper - 1], self.stops[upper]);
+        let f = (x - a.0) / (b.0 - a.0);
+        (1.0 - f) * a.1 + f * b.1
+    }
+}
+
+// Any scalar texture coloured through a ramp.
+#[derive(Clone, Default)]
+pub struct ColorRamp<T: Texture> {
+    pub input: T,
+    pub ramp: Ramp,
+}
+
+impl<T: Texture> ColorRamp<T> {
+    pub fn new(input: T, ramp: Ramp) -> Self {
+        Self { input, ramp }
+    }
+}
+
+impl<T: Texture> Texture for ColorRamp<T> {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        self.ramp.value(self.input.scalar(u, v, p))
+    }
+
+    fn filtered(&self, u: f64, v: f64, p: &Point3, footprint: &Footprint) -> Color {
+        let c = self.input.filtered(u, v, p, footprint);
+        self.ramp.value((c.x() + c.y() + c.z()) / 3.0)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Fractal {
+    Fbm,
+    Ridged,
+    Turbulence,
+}
+
+// Grey fractal noise in [0, 1] over space, scale being the frequency of the first octave.
+#[derive(Clone)]
+pub struct FractalTexture<N: Noise> {
+    pub noise: N,
+    pub fractal: Fractal,
+    pub scale: f64,
+    pub octaves: usize,
+    pub lacunarity: f64,
+    pub gain: f64,
+}
+
+impl<N: Noise> FractalTexture<N> {
+    pub fn new(noise: N, fractal: Fractal, scale: f64) -> Self {
+        Self {
+            noise,
+            fractal,
+            scale,
+            octaves: 6,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+}
+
+impl<N: Noise> Texture for FractalTexture<N> {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let p = *p * self.scale;
+        let value = match self.fractal {
+            Fractal::Fbm => {
+                0.5 * (1.0 + self.noise.fbm(&p, self.octaves, self.lacunarity, self.gain))
+            }
+            Fractal::Ridged => self
+                .noise
+                .ridged(&p, self.octaves, self.lacunarity, self.gain, 1.0),
+            Fractal::Turbulence => self.noise.turbulence(&p, self.octaves),
+        };
+        Color::same(value.max(0.0).min(1.0))
+    }
+}
+
+// Bands across the Z axis, bent by turbulence, as NoiseTexture but coloured by a ramp. Scale is
+// the frequency of the bands and distortion how far the turbulence moves them.
+#[derive(Clone)]
+pub struct MarbleTexture {
+    pub noise: Perlin,
+    pub ramp: Ramp,
+    pub scale: f64,
+    pub distortion: f64,
+    pub frequency: f64, //of the turbulence
+}
+
+impl MarbleTexture {
+    pub fn new(ramp: Ramp, scale: f64, seed: u64) -> Self {
+        Self {
+            noise: Perlin::with_seed(seed),
+            ramp,
+            scale,
+            distortion: 10.0,
+            frequency: 1.0,
+        }
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let turbulence = self.noise.turbulence(&(*p * self.frequency), 7);
+        let bands = (self.scale * p.z() + self.distortion * turbulence).sin();
+        self.ramp.value(0.5 * (1.0 + bands))
+    }
+}
+
+// Growth rings around the Y axis, rings to a unit of radius, wobbled by noise. The ramp goes
+// through one ring, from its inside outwards.
+#[derive(Clone)]
+pub struct WoodTexture {
+    pub noise: Perlin,
+    pub ramp: Ramp,
+    pub rings: f64,
+    pub distortion: f64, //in rings
+    pub frequency: f64,  //of the wobble
+}
+
+impl WoodTexture {
+    pub fn new(ramp: Ramp, rings: f64, seed: u64) -> Self {
+        Self {
+            noise: Perlin::with_seed(seed),
+            ramp,
+            rings,
+            distortion: 0.4,
+            frequency: 1.0,
+        }
+    }
+}
+
+impl Texture for WoodTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let radius = (p.x() * p.x() + p.z() * p.z()).sqrt();
+        let wobble = self.noise.fbm(&(*p * self.frequency), 4, 2.0, 0.5);
+        let ring = radius * self.rings + self.distortion * wobble;
+        self.ramp.value(ring - ring.floor())
+    }
+}
+
+// Crystals of a random shade each, from Worley cells scale to a unit, with fine noise over
+// them. Roughness is the share of the noise.
+#[derive(Clone)]
+pub struct GraniteTexture {
+    pub cells: Worley,
+    pub noise: Perlin,
+    pub ramp: Ramp,
+    pub scale: f64,
+    pub roughness: f64,
+}
+
+impl GraniteTexture {
+    pub fn new(ramp: Ramp, scale: f64, seed: u64) -> Self {
+        Self {
+            cells: Worley::with_seed(seed),
+            noise: Perlin::with_seed(seed.wrapping_add(1)),
+            ramp,
+            scale,
+            roughness: 0.3,
+        }
+    }
+}
+
+impl Texture for GraniteTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let p = *p * self.scale;
+        let (_, _, crystal) = self.cells.cells(&p);
+        let grain = 0.5 * (1.0 + self.noise.fbm(&(p * 4.0), 4, 2.0, 0.5));
+        let value = (1.0 - self.roughness) * crystal + self.roughness * grain;
+        self.ramp.value(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ramp() -> Ramp {
+        Ramp::new(vec![
+            (0.8, Color::new(0.0, 0.0, 1.0)),
+            (0.2, Color::new(1.0, 0.0, 0.0)),
+            (0.4, Color::new(0.0, 1.0, 0.0)),
+        ])
+    }
+
+    fn assert_color(c: Color, expected: Color) {
+        assert!((c - expected).length() < 1e-9, "{:?} != {:?}", c, expected);
+    }
+
+    #[test]
+    fn ramp_holds_the_ends() {
+        let ramp = ramp();
+        assert_color(ramp.value(-1.0), Color::new(1.0, 0.0, 0.0));
+        assert_color(ramp.value(0.2), Color::new(1.0, 0.0, 0.0));
+        assert_color(ramp.value(0.8), Color::new(0.0, 0.0, 1.0));
+        assert_color(ramp.value(2.0), Color::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn ramp_blends_between_stops() {
+        let ramp = ramp();
+        assert_color(ramp.value(0.3), Color::new(0.5, 0.5, 0.0));
+        assert_color(ramp.value(0.4), Color::new(0.0, 1.0, 0.0));
+        assert_color(ramp.value(0.7), Color::new(0.0, 0.25, 0.75));
+    }
+
+    #[test]
+    fn ramp_of_nan_is_an_end_stop() {
+        assert_color(ramp().value(f64::NAN), Color::new(1.0, 0.0, 0.0));
+        assert_color(Ramp::default().value(f64::NAN), Color::default());
+    }
+}
@@ -0,0 +1,54 @@
+use crate::utility::vec3::*;
+
+//CIE XYZ to linear sRGB (D65)
+pub fn xyz_to_rgb(xyz: &Vec3) -> Color {
+    Color::new(
+        3.2406 * xyz.x() - 1.5372 * xyz.y() - 0.4986 * xyz.z(),
+        -0.9689 * xyz.x() + 1.8758 * xyz.y() + 0.0415 * xyz.z(),
+        0.0557 * xyz.x() - 0.2040 * xyz.y() + 1.0570 * xyz.z(),
+    )
+}
+
+//chromaticity of a black body on the Planckian locus (Kim et al.), valid in [1667K, 25000K]
+pub fn planckian_xy(temperature: f64) -> (f64, f64) {
+    let t = temperature.max(1667.0).min(25000.0);
+    let (t2, t3) = (t * t, t * t * t);
+    let x = if t <= 4000.0 {
+        -0.2661239e9 / t3 - 0.2343589e6 / t2 + 0.8776956e3 / t + 0.179910
+    } else {
+        -3.0258469e9 / t3 + 2.1070379e6 / t2 + 0.2226347e3 / t + 0.240390
+    };
+    let (x2, x3) = (x * x, x * x * x);
+    let y = if t <= 2222.0 {
+        -1.1063814 * x3 - 1.34811020 * x2 + 2.18555832 * x - 0.20219683
+    } else if t <= 4000.0 {
+        -0.9549476 * x3 - 1.37418593 * x2 + 2.09137015 * x - 0.16748867
+    } else {
+        3.0817580 * x3 - 5.87338670 * x2 + 3.75112997 * x - 0.37001483
+    };
+    (x, y)
+}
+
+//linear sRGB colour of a black body with unit luminance
+pub fn blackbody_rgb(temperature: f64) -> Color {
+    let (x, y) = planckian_xy(temperature);
+    xyz_to_rgb(&Vec3::new(x / y, 1.0, (1.0 - x - y) / y))
+}
+
+//relative luminance of a linear sRGB colour
+pub fn luminance(c: &Color) -> f64 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
+//CIE 1931 colour matching functions at a wavelength in nm, multi-lobe fit by Wyman et al.
+pub fn cie_xyz(lambda: f64) -> Vec3 {
+    let g = |mu: f64, sigma1: f64, sigma2: f64| {
+        let t = (lambda - mu) / if lambda < mu { sigma1 } else { sigma2 };
+        (-0.5 * t * t).exp()
+    };
+    Vec3::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
@@ -1,10 +1,15 @@
 use crate::utility::vec3::*;
 
+// Spread below which a diffuse or glossy bounce does not go, in radians. The light it gathers
+// comes from a wide lobe, so texture detail seen through it is blurred anyway.
+const ROUGH_SPREAD: f64 = 0.05;
+
 #[derive(Debug, Copy, Clone, Default)]
 pub struct Ray {
     pub orig: Point3,
     pub dir: Vec3,
     pub tm: f64,
+    pub cone: RayCone, //footprint of the ray for texture filtering, zero is a thin ray
 }
 
 impl Ray {
@@ -13,9 +18,15 @@ impl Ray {
             orig: *orig,
             dir: *dir,
             tm: time,
+            cone: RayCone::default(),
         }
     }
 
+    pub fn with_cone(mut self, cone: RayCone) -> Self {
+        self.cone = cone;
+        self
+    }
+
     pub fn origin(&self) -> Point3 {
         self.orig
     }
@@ -40,3 +51,33 @@ impl Ray {
         self.tm
     }
 }
+
+// Ray cone (Akenine-Moller et al. 2019): the width of the beam a ray stands for grows linearly
+// with the distance travelled, spread is the angle it grows by.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct RayCone {
+    pub width: f64,
+    pub spread: f64,
+}
+
+impl RayCone {
+    pub fn new(width: f64, spread: f64) -> Self {
+        Self { width, spread }
+    }
+
+    pub fn width_at(&self, distance: f64) -> f64 {
+        (self.width + self.spread * distance).abs()
+    }
+
+    //the cone leaving a surface at this distance, curvature is ignored
+    pub fn bounce(&self, distance: f64, specular: bool) -> Self {
+        Self {
+            width: self.width_at(distance),
+            spread: if specular {
+                self.spread
+            } else {
+                self.spread.max(ROUGH_SPREAD)
+            },
+        }
+    }
+}
@@ -44,14 +44,14 @@ pub fn bvh_build_static(objects: &mut Vec<Object>, start: usize, end: usize) ->
             }
             let left = left.code.clone();
             let right = right.code.clone();
-            quote! (Box::new(BVHNode::construct(Some(#left), Some(#right), 0.0, 1.0)))
+            quote! (Box::new(BVHNode::construct(Some(#left), Some(#right), 0.0, 0.0)))
         }
         _other => {
             objects[start..end].sort_by(|a, b| box_compare_order(a, b, axis));
             let mid = start + span / 2;
             let right = bvh_build_static(objects, mid, end);
             let left = bvh_build_static(objects, start, mid);
-            quote! (Box::new(BVHNode::construct(Some(#left), Some(#right), 0.0, 1.0)))
+            quote! (Box::new(BVHNode::construct(Some(#left), Some(#right), 0.0, 0.0)))
         }
     }
 }
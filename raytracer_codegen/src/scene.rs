@@ -36,7 +36,7 @@ pub fn define_static_final_scene() -> proc_macro::TokenStream {
         &Point3::new(430., 400., 200.),
         50.,
         0.,
-        1.,
+        CameraSettings::default().shutter_time,
         #moving_sphere_material,
     )));
 
@@ -70,10 +70,11 @@ pub fn define_static_final_scene() -> proc_macro::TokenStream {
         Dielectric::new(1.5)
     ));
 
-    let medium2_code = quote!(Box::new(ConstantMedium::new_from_color(
+    let medium2_code = quote!(Box::new(ConstantMedium::new_from_color_with_phase(
         #boundary_obj2_code,
         0.0001,
         &Color::new(1., 1., 1.),
+        HenyeyGreenstein::new(0.7),
     )));
 
     let earth_material_code = quote!(Lambertian::new(ImageTexture::new(